@@ -5,7 +5,6 @@ use dms_awww::config::Config;
 use dms_awww::dms::{DmsSession, SessionJson};
 use dms_awww::executor::Executor;
 use std::fs;
-use std::path::Path;
 use std::time::Duration;
 use tempfile::TempDir;
 
@@ -167,6 +166,7 @@ fn bench_memory_patterns(c: &mut Criterion) {
             let change = dms_awww::dms::WallpaperChange {
                 wallpapers: vec![dms_awww::dms::Wallpaper::new("/tmp/wp.jpg".to_string())],
                 is_light_mode: false,
+                matugen_scheme: "scheme-tonal-spot".to_string(),
             };
             black_box(change)
         })
@@ -181,6 +181,7 @@ fn bench_memory_patterns(c: &mut Criterion) {
                     dms_awww::dms::Wallpaper::for_monitor("/tmp/wp3.jpg".to_string(), "eDP-1".to_string()),
                 ],
                 is_light_mode: true,
+                matugen_scheme: "scheme-tonal-spot".to_string(),
             };
             black_box(change)
         })
@@ -268,6 +269,7 @@ fn bench_wallpaper_throughput(c: &mut Criterion) {
                     let change = dms_awww::dms::WallpaperChange {
                         wallpapers: wallpapers.clone(),
                         is_light_mode: false,
+                        matugen_scheme: "scheme-tonal-spot".to_string(),
                     };
                     black_box(change)
                 })
@@ -4,7 +4,6 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion, Benchmark
 use dms_awww::config::Config;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
 use tempfile::TempDir;
 
 fn bench_config_load_default(c: &mut Criterion) {
@@ -29,7 +28,6 @@ fn bench_config_load_default(c: &mut Criterion) {
 }
 
 fn bench_config_path_expansion(c: &mut Criterion) {
-    let home = env::var("HOME").unwrap();
     let test_cases = vec![
         ("~/.config/test", "tilde expansion"),
         ("/tmp/test", "absolute path"),
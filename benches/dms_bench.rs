@@ -1,10 +1,9 @@
 // DMS parsing benchmarks using criterion
 
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use dms_awww::config::Config;
-use dms_awww::dms::{DmsSession, SessionJson, SettingsJson};
+use dms_awww::dms::{ChangeSet, DmsSession, SessionJson, SettingsJson};
 use std::fs;
-use std::path::Path;
 use tempfile::TempDir;
 
 fn bench_parse_session_json(c: &mut Criterion) {
@@ -150,7 +149,7 @@ fn bench_session_get_current_state(c: &mut Criterion) {
     group.finish();
 }
 
-fn bench_session_has_changed(c: &mut Criterion) {
+fn bench_session_change_set(c: &mut Criterion) {
     let temp_dir = TempDir::new().unwrap();
     let mut config = Config::default();
     config.dms.session_file = temp_dir.path().join("session.json").to_str().unwrap().to_string();
@@ -164,14 +163,14 @@ fn bench_session_has_changed(c: &mut Criterion) {
     fs::write(temp_dir.path().join("session.json"), json).unwrap();
     fs::write(temp_dir.path().join("settings.json"), r#"{"matugenScheme":"scheme-tonal-spot"}"#).unwrap();
 
-    c.bench_function("session_has_changed_no_change", |b| {
+    c.bench_function("session_change_set_no_change", |b| {
         b.iter(|| {
-            let mut session = DmsSession::new(config.clone());
-            // First call
-            let _ = session.has_changed();
-            // Second call (no change)
-            let changed = session.has_changed();
-            black_box(changed)
+            let session = DmsSession::new(config.clone());
+            // First read
+            let previous = session.get_current_state().unwrap();
+            // Second read (no change)
+            let current = session.get_current_state().unwrap();
+            black_box(ChangeSet::between(&previous, &current))
         })
     });
 }
@@ -251,7 +250,7 @@ criterion_group!(
     bench_parse_session_json,
     bench_parse_settings_json,
     bench_session_get_current_state,
-    bench_session_has_changed,
+    bench_session_change_set,
     bench_session_read_file,
     bench_wallpaper_validation,
     bench_session_helpers
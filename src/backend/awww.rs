@@ -0,0 +1,243 @@
+//! awww backend (default)
+
+use super::{run_command, BackendFuture, OutputContent, WallpaperBackend};
+use crate::config::AwwwConfig;
+use crate::error::DmsAwwwError;
+use crate::executor::ApplyTrigger;
+use crate::supervisor::AwwwSupervisor;
+use std::collections::HashMap;
+
+/// Backend driving `awww img` / `awww clear` against a running awww-daemon
+pub struct AwwwBackend {
+    config: AwwwConfig,
+}
+
+impl AwwwBackend {
+    /// Create a new awww backend
+    pub fn new(config: AwwwConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the full awww argument list for one monitor
+    ///
+    /// Layout: `[extra_args] img -o <monitor> [transition] [img_args] <path>`.
+    /// The transition is `[awww.transition]`, then the monitor's overrides,
+    /// then the override for the apply trigger (if any).
+    fn img_args(&self, path: &str, monitor: &str, trigger: ApplyTrigger) -> Vec<String> {
+        let awww = &self.config;
+
+        let mut transition = awww.transition_for(monitor);
+        match trigger {
+            ApplyTrigger::Startup => transition = transition.merged(&awww.events.startup),
+            ApplyTrigger::ModeChange => transition = transition.merged(&awww.events.mode_change),
+            ApplyTrigger::Change => {}
+        }
+
+        let mut args = awww.extra_args.clone();
+        args.extend(["img".to_string(), "-o".to_string(), monitor.to_string()]);
+        args.extend(transition.to_args());
+        args.extend(awww.img_args_for(monitor));
+        args.push(path.to_string());
+        args
+    }
+
+    /// Build the awww argument list that fills one monitor with a solid color
+    ///
+    /// Layout: `[extra_args] clear -o <monitor> <rrggbb>`.
+    fn clear_args(&self, color: &str, monitor: &str) -> Vec<String> {
+        let mut args = self.config.extra_args.clone();
+        args.extend([
+            "clear".to_string(),
+            "-o".to_string(),
+            monitor.to_string(),
+            color.trim_start_matches('#').to_string(),
+        ]);
+        args
+    }
+}
+
+impl WallpaperBackend for AwwwBackend {
+    fn name(&self) -> &'static str {
+        "awww"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        if self.config.daemon.manage {
+            vec!["awww", "awww-daemon"]
+        } else {
+            vec!["awww"]
+        }
+    }
+
+    fn apply<'a>(&'a self, output: &'a str, image: &'a str, trigger: ApplyTrigger) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            run_command("awww", &self.img_args(image, output, trigger)).await?;
+            Ok(())
+        })
+    }
+
+    fn clear<'a>(&'a self, output: &'a str, color: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            run_command("awww", &self.clear_args(color, output)).await?;
+            Ok(())
+        })
+    }
+
+    fn health(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            if AwwwSupervisor::probe(&self.config.extra_args).await {
+                Ok(())
+            } else {
+                Err(DmsAwwwError::DaemonUnavailable(
+                    "awww query failed".to_string(),
+                ))
+            }
+        })
+    }
+
+    fn current(&self) -> BackendFuture<'_, HashMap<String, OutputContent>> {
+        Box::pin(async move {
+            let mut args = self.config.extra_args.clone();
+            args.push("query".to_string());
+            Ok(parse_awww_query(&run_command("awww", &args).await?))
+        })
+    }
+}
+
+/// Parse `awww query` output into what each output is displaying
+///
+/// Lines look like `DP-1: 2560x1440, scale: 1, currently displaying: image: /path`,
+/// optionally prefixed by a namespace field (`: DP-1: ...` or `ns: DP-1: ...`).
+pub fn parse_awww_query(stdout: &str) -> HashMap<String, OutputContent> {
+    let mut outputs = HashMap::new();
+
+    for line in stdout.lines() {
+        let Some((info, displaying)) = line.split_once("currently displaying:") else {
+            continue;
+        };
+
+        // The output name is the field right before the `WIDTHxHEIGHT` field
+        let fields: Vec<&str> = info.split([':', ',']).map(str::trim).collect();
+        let Some(res_idx) = fields.iter().position(|f| is_resolution(f)) else {
+            continue;
+        };
+        let Some(name) = res_idx.checked_sub(1).map(|i| fields[i]).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+
+        let content = match displaying.trim().split_once(':') {
+            Some(("image", path)) => OutputContent::Image(path.trim().to_string()),
+            Some(("color", color)) => OutputContent::Color(color.trim().to_string()),
+            _ => continue,
+        };
+
+        outputs.insert(name.to_string(), content);
+    }
+
+    outputs
+}
+
+/// Check for a `WIDTHxHEIGHT` field
+fn is_resolution(field: &str) -> bool {
+    field
+        .split_once('x')
+        .map(|(w, h)| {
+            !w.is_empty()
+                && !h.is_empty()
+                && w.chars().all(|c| c.is_ascii_digit())
+                && h.chars().all(|c| c.is_ascii_digit())
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AwwwOutputConfig, TransitionConfig};
+
+    #[test]
+    fn test_img_args_layering() {
+        let mut config = AwwwConfig {
+            extra_args: vec!["--namespace".to_string(), "main".to_string()],
+            img_args: vec!["--resize".to_string(), "crop".to_string()],
+            ..Default::default()
+        };
+        config.transition.transition_type = Some("grow".to_string());
+        config.transition.duration = Some(1.5);
+        config.monitors.insert(
+            "DP-1".to_string(),
+            AwwwOutputConfig {
+                transition: TransitionConfig {
+                    transition_type: Some("wipe".to_string()),
+                    ..Default::default()
+                },
+                img_args: vec!["--fill-color".to_string(), "000000".to_string()],
+            },
+        );
+        let backend = AwwwBackend::new(config);
+
+        assert_eq!(
+            backend.img_args("/wp.png", "HDMI-A-1", ApplyTrigger::Change),
+            vec![
+                "--namespace", "main", "img", "-o", "HDMI-A-1",
+                "--transition-type", "grow", "--transition-duration", "1.5",
+                "--resize", "crop", "/wp.png",
+            ]
+        );
+        assert_eq!(
+            backend.img_args("/wp.png", "DP-1", ApplyTrigger::Change),
+            vec![
+                "--namespace", "main", "img", "-o", "DP-1",
+                "--transition-type", "wipe", "--transition-duration", "1.5",
+                "--resize", "crop", "--fill-color", "000000", "/wp.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_img_args_event_overrides() {
+        let backend = AwwwBackend::new(AwwwConfig::default());
+
+        let startup = backend.img_args("/wp.png", "DP-1", ApplyTrigger::Startup);
+        assert!(startup.windows(2).any(|w| w == ["--transition-type", "none"]));
+
+        let mode = backend.img_args("/wp.png", "DP-1", ApplyTrigger::ModeChange);
+        assert!(mode.windows(2).any(|w| w == ["--transition-type", "fade"]));
+
+        let change = backend.img_args("/wp.png", "DP-1", ApplyTrigger::Change);
+        assert!(!change.iter().any(|a| a == "--transition-type"));
+    }
+
+    #[test]
+    fn test_clear_args() {
+        let config = AwwwConfig {
+            extra_args: vec!["--namespace".to_string(), "main".to_string()],
+            ..Default::default()
+        };
+        let backend = AwwwBackend::new(config);
+
+        assert_eq!(
+            backend.clear_args("#1e1e2e", "DP-1"),
+            vec!["--namespace", "main", "clear", "-o", "DP-1", "1e1e2e"]
+        );
+    }
+
+    #[test]
+    fn test_parse_awww_query() {
+        let stdout = "\
+DP-1: 2560x1440, scale: 1, currently displaying: image: /home/u/Pictures/a b.png
+: HDMI-A-1: 1920x1080, scale: 1.5, currently displaying: color: 1e1e2e
+main: eDP-1: 1920x1200, scale: 2, currently displaying: image: /tmp/c.jpg
+garbage line
+";
+        let outputs = parse_awww_query(stdout);
+
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(
+            outputs.get("DP-1"),
+            Some(&OutputContent::Image("/home/u/Pictures/a b.png".to_string()))
+        );
+        assert_eq!(outputs.get("HDMI-A-1"), Some(&OutputContent::Color("1e1e2e".to_string())));
+        assert_eq!(outputs.get("eDP-1"), Some(&OutputContent::Image("/tmp/c.jpg".to_string())));
+    }
+}
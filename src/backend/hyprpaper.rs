@@ -0,0 +1,113 @@
+//! hyprpaper backend (IPC via `hyprctl hyprpaper`)
+
+use super::{run_command, unsupported, BackendFuture, OutputContent, WallpaperBackend};
+use crate::config::HyprpaperConfig;
+use crate::executor::ApplyTrigger;
+use std::collections::HashMap;
+
+/// Backend talking to a running hyprpaper through `hyprctl hyprpaper`
+pub struct HyprpaperBackend {
+    config: HyprpaperConfig,
+}
+
+impl HyprpaperBackend {
+    /// Create a new hyprpaper backend
+    pub fn new(config: HyprpaperConfig) -> Self {
+        Self { config }
+    }
+
+    /// Run `hyprctl hyprpaper <args...>`
+    async fn hyprctl(args: &[&str]) -> crate::error::Result<String> {
+        let mut full = vec!["hyprpaper".to_string()];
+        full.extend(args.iter().map(|a| a.to_string()));
+        run_command("hyprctl", &full).await
+    }
+
+    /// Argument for `hyprctl hyprpaper wallpaper`; an empty output means all
+    fn wallpaper_arg(&self, output: &str, image: &str) -> String {
+        let output = if output == "ALL" { "" } else { output };
+        match &self.config.fit_mode {
+            Some(mode) => format!("{output},{mode}:{image}"),
+            None => format!("{output},{image}"),
+        }
+    }
+}
+
+impl WallpaperBackend for HyprpaperBackend {
+    fn name(&self) -> &'static str {
+        "hyprpaper"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["hyprctl"]
+    }
+
+    fn apply<'a>(&'a self, output: &'a str, image: &'a str, _trigger: ApplyTrigger) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            Self::hyprctl(&["preload", image]).await?;
+            Self::hyprctl(&["wallpaper", &self.wallpaper_arg(output, image)]).await?;
+
+            if self.config.unload_unused {
+                if let Err(e) = Self::hyprctl(&["unload", "unused"]).await {
+                    tracing::debug!("hyprpaper unload failed: {}", e);
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn clear<'a>(&'a self, _output: &'a str, _color: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async { Err(unsupported("hyprpaper", "solid colors")) })
+    }
+
+    fn health(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async {
+            Self::hyprctl(&["listactive"]).await?;
+            Ok(())
+        })
+    }
+
+    fn current(&self) -> BackendFuture<'_, HashMap<String, OutputContent>> {
+        Box::pin(async {
+            Ok(parse_listactive(&Self::hyprctl(&["listactive"]).await?))
+        })
+    }
+}
+
+/// Parse `hyprctl hyprpaper listactive` lines of the form `DP-1 = /path`
+fn parse_listactive(stdout: &str) -> HashMap<String, OutputContent> {
+    stdout
+        .lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(output, path)| {
+            // Strip a `mode:` prefix such as `contain:/path`
+            let path = path.trim();
+            let path = path.split_once(":/").map(|(_, p)| format!("/{p}")).unwrap_or(path.to_string());
+            (output.trim().to_string(), OutputContent::Image(path))
+        })
+        .filter(|(output, _)| !output.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallpaper_arg() {
+        let mut backend = HyprpaperBackend::new(HyprpaperConfig::default());
+        assert_eq!(backend.wallpaper_arg("DP-1", "/tmp/a.png"), "DP-1,/tmp/a.png");
+        assert_eq!(backend.wallpaper_arg("ALL", "/tmp/a.png"), ",/tmp/a.png");
+
+        backend.config.fit_mode = Some("contain".to_string());
+        assert_eq!(backend.wallpaper_arg("DP-1", "/tmp/a.png"), "DP-1,contain:/tmp/a.png");
+    }
+
+    #[test]
+    fn test_parse_listactive() {
+        let outputs = parse_listactive("DP-1 = /tmp/a.png\nHDMI-A-1 = contain:/tmp/b.png\n");
+        assert_eq!(outputs.get("DP-1"), Some(&OutputContent::Image("/tmp/a.png".to_string())));
+        assert_eq!(outputs.get("HDMI-A-1"), Some(&OutputContent::Image("/tmp/b.png".to_string())));
+    }
+}
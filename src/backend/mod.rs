@@ -0,0 +1,165 @@
+//! Wallpaper backends
+//!
+//! This module defines the `WallpaperBackend` trait used by the executor to
+//! put images and solid colors on outputs, along with the awww (default),
+//! swaybg, hyprpaper and wpaperd implementations selected by `[backend] kind`.
+
+mod awww;
+mod hyprpaper;
+mod swaybg;
+mod wpaperd;
+
+pub use awww::{parse_awww_query, AwwwBackend};
+pub use hyprpaper::HyprpaperBackend;
+pub use swaybg::SwaybgBackend;
+pub use wpaperd::WpaperdBackend;
+
+use crate::config::{BackendKind, Config};
+use crate::dms::Wallpaper;
+use crate::error::{DmsAwwwError, Result};
+use crate::executor::ApplyTrigger;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::process::Command;
+use which::which;
+
+/// Boxed future returned by backend operations
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// What an output is currently displaying
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputContent {
+    /// Image file path
+    Image(String),
+    /// Solid color as `rrggbb`
+    Color(String),
+}
+
+impl OutputContent {
+    /// Check whether this is what `wallpaper` would put on screen
+    pub fn matches(&self, wallpaper: &Wallpaper) -> bool {
+        match (self, wallpaper.color()) {
+            (OutputContent::Color(current), Some(color)) => {
+                current.eq_ignore_ascii_case(color.trim_start_matches('#'))
+            }
+            (OutputContent::Image(current), None) => {
+                current == &wallpaper.path || same_file(current, &wallpaper.path)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compare two paths after resolving symlinks and relative components
+fn same_file(a: &str, b: &str) -> bool {
+    match (Path::new(a).canonicalize(), Path::new(b).canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// A program that can display wallpapers on outputs
+///
+/// The output name `ALL` means every output.
+pub trait WallpaperBackend: Send + Sync {
+    /// Short name used in logs and errors
+    fn name(&self) -> &'static str;
+
+    /// Commands that must be on PATH for this backend
+    fn required_commands(&self) -> Vec<&'static str>;
+
+    /// Display `image` on `output`
+    fn apply<'a>(&'a self, output: &'a str, image: &'a str, trigger: ApplyTrigger) -> BackendFuture<'a, ()>;
+
+    /// Fill `output` with `color` (`#rrggbb`)
+    fn clear<'a>(&'a self, output: &'a str, color: &'a str) -> BackendFuture<'a, ()>;
+
+    /// Check that the backend is able to accept requests
+    fn health(&self) -> BackendFuture<'_, ()>;
+
+    /// What each output currently displays, if the backend can tell
+    ///
+    /// An empty map means unknown, so every output gets applied.
+    fn current(&self) -> BackendFuture<'_, HashMap<String, OutputContent>> {
+        Box::pin(async { Ok(HashMap::new()) })
+    }
+}
+
+/// Create the backend selected by `[backend] kind`
+pub fn create_backend(config: &Config) -> Arc<dyn WallpaperBackend> {
+    match config.backend.kind {
+        BackendKind::Awww => Arc::new(AwwwBackend::new(config.awww.clone())),
+        BackendKind::Swaybg => Arc::new(SwaybgBackend::new(config.backend.swaybg.clone())),
+        BackendKind::Hyprpaper => Arc::new(HyprpaperBackend::new(config.backend.hyprpaper.clone())),
+        BackendKind::Wpaperd => Arc::new(WpaperdBackend::new(config.backend.wpaperd.clone())),
+    }
+}
+
+/// Check that every command in `commands` is on PATH
+pub fn check_commands(commands: &[&str]) -> Result<()> {
+    for &cmd in commands {
+        let path = which(cmd).map_err(|_| DmsAwwwError::CommandNotFound(cmd.to_string()))?;
+        tracing::debug!("{} found: {}", cmd, path.display());
+    }
+    Ok(())
+}
+
+/// Run a command to completion, mapping a non-zero exit to `CommandFailed`
+pub(crate) async fn run_command(program: &str, args: &[String]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let code = output.status.code().unwrap_or(-1);
+        tracing::error!("{} failed: {}", program, stderr.trim());
+        return Err(DmsAwwwError::CommandFailed(program.to_string(), code));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Error for an operation a backend cannot perform
+pub(crate) fn unsupported(backend: &str, operation: &str) -> DmsAwwwError {
+    DmsAwwwError::BackendUnsupported {
+        backend: backend.to_string(),
+        operation: operation.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_content_matches() {
+        let image = Wallpaper::new("/tmp/a.png".to_string());
+        let color = Wallpaper::new("#1e1e2e".to_string());
+
+        assert!(OutputContent::Image("/tmp/a.png".to_string()).matches(&image));
+        assert!(!OutputContent::Image("/tmp/b.png".to_string()).matches(&image));
+        assert!(OutputContent::Color("1E1E2E".to_string()).matches(&color));
+        assert!(!OutputContent::Color("1e1e2e".to_string()).matches(&image));
+    }
+
+    #[test]
+    fn test_create_backend_kind() {
+        let mut config = Config::default();
+        assert_eq!(create_backend(&config).name(), "awww");
+
+        config.backend.kind = BackendKind::Swaybg;
+        assert_eq!(create_backend(&config).name(), "swaybg");
+
+        config.backend.kind = BackendKind::Hyprpaper;
+        assert_eq!(create_backend(&config).name(), "hyprpaper");
+
+        config.backend.kind = BackendKind::Wpaperd;
+        assert_eq!(create_backend(&config).name(), "wpaperd");
+    }
+}
@@ -0,0 +1,183 @@
+//! swaybg backend
+//!
+//! swaybg has no IPC, so one child process is kept per output and replaced
+//! on every change. The new process is started before the old one is killed
+//! so the output never flashes empty.
+
+use super::{BackendFuture, OutputContent, WallpaperBackend};
+use crate::config::SwaybgConfig;
+use crate::error::{DmsAwwwError, Result};
+use crate::executor::ApplyTrigger;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, Duration};
+use which::which;
+
+/// Time given to a new swaybg to draw before the previous one is killed
+const HANDOVER_DELAY: Duration = Duration::from_millis(200);
+
+/// A running swaybg process and what it displays
+struct Managed {
+    child: Child,
+    content: OutputContent,
+}
+
+/// Backend managing one swaybg child process per output
+pub struct SwaybgBackend {
+    config: SwaybgConfig,
+    children: Mutex<HashMap<String, Managed>>,
+}
+
+impl SwaybgBackend {
+    /// Create a new swaybg backend
+    pub fn new(config: SwaybgConfig) -> Self {
+        Self {
+            config,
+            children: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// swaybg arguments for an output; `ALL` maps to swaybg's `*`
+    fn args(&self, output: &str, content: &OutputContent) -> Vec<String> {
+        let output = if output == "ALL" { "*" } else { output };
+        let mut args = vec!["-o".to_string(), output.to_string()];
+
+        match content {
+            OutputContent::Image(path) => {
+                args.extend(["-i".to_string(), path.clone()]);
+                args.extend(["-m".to_string(), self.config.mode.clone()]);
+            }
+            OutputContent::Color(color) => {
+                args.extend(["-c".to_string(), format!("#{color}")]);
+            }
+        }
+
+        args
+    }
+
+    /// Start swaybg for an output and retire the process it replaces
+    ///
+    /// The new process is tracked before the handover, so an apply aborted
+    /// meanwhile can't leak it; the processes it replaces are killed when
+    /// dropped, on abort too.
+    async fn replace(&self, output: &str, content: OutputContent) -> Result<()> {
+        let child = Command::new("swaybg")
+            .args(self.args(output, &content))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // `ALL` covers every output, so it replaces per-output processes too
+        let mut previous: Vec<(String, Managed)> = {
+            let mut children = self.children.lock().unwrap();
+            let previous: Vec<(String, Managed)> = if output == "ALL" {
+                children.drain().collect()
+            } else {
+                children.remove_entry(output).into_iter().collect()
+            };
+            children.insert(output.to_string(), Managed { child, content });
+            previous
+        };
+
+        sleep(HANDOVER_DELAY).await;
+
+        // swaybg exits right away on bad arguments or unreadable images
+        let exited = {
+            let mut children = self.children.lock().unwrap();
+            let status = match children.get_mut(output) {
+                Some(managed) => managed.child.try_wait()?,
+                None => None,
+            };
+            if status.is_some() {
+                // Keep showing what was there before
+                children.remove(output);
+                children.extend(previous.drain(..));
+            }
+            status
+        };
+        if let Some(status) = exited {
+            return Err(DmsAwwwError::CommandFailed(
+                "swaybg".to_string(),
+                status.code().unwrap_or(-1),
+            ));
+        }
+
+        for (_, mut managed) in previous {
+            let _ = managed.child.start_kill();
+            let _ = managed.child.wait().await;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SwaybgBackend {
+    /// Stop the swaybg processes this backend started
+    fn drop(&mut self) {
+        let children = self.children.get_mut().unwrap_or_else(|e| e.into_inner());
+        for managed in children.values_mut() {
+            let _ = managed.child.start_kill();
+        }
+    }
+}
+
+impl WallpaperBackend for SwaybgBackend {
+    fn name(&self) -> &'static str {
+        "swaybg"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["swaybg"]
+    }
+
+    fn apply<'a>(&'a self, output: &'a str, image: &'a str, _trigger: ApplyTrigger) -> BackendFuture<'a, ()> {
+        Box::pin(self.replace(output, OutputContent::Image(image.to_string())))
+    }
+
+    fn clear<'a>(&'a self, output: &'a str, color: &'a str) -> BackendFuture<'a, ()> {
+        let color = color.trim_start_matches('#').to_string();
+        Box::pin(self.replace(output, OutputContent::Color(color)))
+    }
+
+    fn health(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async {
+            which("swaybg").map_err(|_| DmsAwwwError::CommandNotFound("swaybg".to_string()))?;
+            Ok(())
+        })
+    }
+
+    fn current(&self) -> BackendFuture<'_, HashMap<String, OutputContent>> {
+        let mut children = self.children.lock().unwrap();
+
+        // Forget processes that died so their outputs get re-applied
+        children.retain(|_, m| matches!(m.child.try_wait(), Ok(None)));
+
+        let current = children
+            .iter()
+            .map(|(output, m)| (output.clone(), m.content.clone()))
+            .collect();
+        Box::pin(async move { Ok(current) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swaybg_args() {
+        let backend = SwaybgBackend::new(SwaybgConfig::default());
+
+        assert_eq!(
+            backend.args("DP-1", &OutputContent::Image("/tmp/a.png".to_string())),
+            vec!["-o", "DP-1", "-i", "/tmp/a.png", "-m", "fill"]
+        );
+        assert_eq!(
+            backend.args("ALL", &OutputContent::Color("1e1e2e".to_string())),
+            vec!["-o", "*", "-c", "#1e1e2e"]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! wpaperd backend
+//!
+//! wpaperd reloads its config file when it changes, so this backend owns a
+//! wpaperd config file and rewrites it with one section per output.
+
+use super::{unsupported, BackendFuture, OutputContent, WallpaperBackend};
+use crate::config::{Config, WpaperdConfig};
+use crate::error::Result;
+use crate::executor::ApplyTrigger;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::Mutex;
+
+/// Backend that drives wpaperd through its hot-reloaded config file
+pub struct WpaperdBackend {
+    config: WpaperdConfig,
+    /// Wallpaper per output as last written; held across each write so
+    /// writes land in order
+    outputs: Mutex<BTreeMap<String, String>>,
+}
+
+impl WpaperdBackend {
+    /// Create a new wpaperd backend
+    pub fn new(config: WpaperdConfig) -> Self {
+        Self {
+            config,
+            outputs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Render the wpaperd config; `ALL` maps to the `[default]` section
+    fn render(&self, outputs: &BTreeMap<String, String>) -> String {
+        let mut out = String::from("# Managed by dms-awww; changes will be overwritten\n");
+
+        for (output, path) in outputs {
+            let section = if output == "ALL" { "default" } else { output };
+            out.push_str(&format!("\n[{}]\npath = {}\n", toml_string(section), toml_string(path)));
+            if let Some(mode) = &self.config.mode {
+                out.push_str(&format!("mode = {}\n", toml_string(mode)));
+            }
+        }
+
+        out
+    }
+
+    /// Write the config file atomically so wpaperd never reads a partial file
+    async fn write(&self, contents: &str) -> Result<()> {
+        let path = std::path::PathBuf::from(Config::expand_path(&self.config.config_file));
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let tmp = path.with_extension("toml.tmp");
+        tokio::fs::write(&tmp, contents).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+}
+
+/// Quote a string as a TOML basic string
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl WallpaperBackend for WpaperdBackend {
+    fn name(&self) -> &'static str {
+        "wpaperd"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["wpaperd", "wpaperctl"]
+    }
+
+    fn apply<'a>(&'a self, output: &'a str, image: &'a str, _trigger: ApplyTrigger) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            // Render and write under one lock, so an older snapshot never overwrites a newer one
+            let mut outputs = self.outputs.lock().await;
+            let mut next = if output == "ALL" { BTreeMap::new() } else { outputs.clone() };
+            next.insert(output.to_string(), image.to_string());
+
+            self.write(&self.render(&next)).await?;
+            *outputs = next;
+            Ok(())
+        })
+    }
+
+    fn clear<'a>(&'a self, _output: &'a str, _color: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async { Err(unsupported("wpaperd", "solid colors")) })
+    }
+
+    fn health(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async {
+            super::run_command("wpaperctl", &["all-wallpapers".to_string()]).await?;
+            Ok(())
+        })
+    }
+
+    fn current(&self) -> BackendFuture<'_, HashMap<String, OutputContent>> {
+        Box::pin(async move {
+            let current = self
+                .outputs
+                .lock()
+                .await
+                .iter()
+                .map(|(output, path)| (output.clone(), OutputContent::Image(path.clone())))
+                .collect();
+            Ok(current)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wpaperd_writes_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_file = dir.path().join("wpaperd").join("config.toml");
+        let backend = WpaperdBackend::new(WpaperdConfig {
+            config_file: config_file.to_str().unwrap().to_string(),
+            mode: Some("center".to_string()),
+        });
+
+        backend.apply("DP-1", "/tmp/a \"b\".png", ApplyTrigger::Change).await.unwrap();
+        backend.apply("HDMI-A-1", "/tmp/c.png", ApplyTrigger::Change).await.unwrap();
+
+        let written = std::fs::read_to_string(&config_file).unwrap();
+        assert!(written.contains("[\"DP-1\"]\npath = \"/tmp/a \\\"b\\\".png\"\nmode = \"center\"\n"));
+        assert!(written.contains("[\"HDMI-A-1\"]\npath = \"/tmp/c.png\"\n"));
+
+        backend.apply("ALL", "/tmp/d.png", ApplyTrigger::Change).await.unwrap();
+        let written = std::fs::read_to_string(&config_file).unwrap();
+        assert!(written.contains("[\"default\"]\npath = \"/tmp/d.png\"\n"));
+        assert!(!written.contains("DP-1"));
+    }
+
+    #[tokio::test]
+    async fn test_wpaperd_late_apply_keeps_newer_outputs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_file = dir.path().join("config.toml");
+        let backend = WpaperdBackend::new(WpaperdConfig {
+            config_file: config_file.to_str().unwrap().to_string(),
+            mode: None,
+        });
+
+        // Created first but polled last, as a retried apply can be
+        let late = backend.apply("DP-1", "/tmp/a.png", ApplyTrigger::Change);
+        backend.apply("HDMI-A-1", "/tmp/c.png", ApplyTrigger::Change).await.unwrap();
+        late.await.unwrap();
+
+        let written = std::fs::read_to_string(&config_file).unwrap();
+        assert!(written.contains("path = \"/tmp/a.png\""));
+        assert!(written.contains("path = \"/tmp/c.png\""));
+        assert_eq!(backend.current().await.unwrap().len(), 2);
+    }
+}
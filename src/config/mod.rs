@@ -5,9 +5,9 @@
 
 use crate::error::{DmsAwwwError, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::env;
-use shellexpand;
 
 /// Default log level
 const DEFAULT_LOG_LEVEL: &str = "info";
@@ -27,11 +27,20 @@ const DEFAULT_CACHE_DIR: &str = "~/.cache/DankMaterialShell";
 /// Default matugen scheme type
 const DEFAULT_MATUGEN_SCHEME: &str = "scheme-tonal-spot";
 
+/// Default wpaperd config file managed by the wpaperd backend
+const DEFAULT_WPAPERD_CONFIG_FILE: &str = "~/.config/wpaperd/config.toml";
+
 /// Default quickshell directory
 const DEFAULT_SHELL_DIR: &str = "/usr/share/quickshell/dms";
 
+/// Transition types accepted by `awww img --transition-type`
+const AWWW_TRANSITION_TYPES: &[&str] = &[
+    "none", "simple", "fade", "left", "right", "top", "bottom", "wipe", "wave", "grow",
+    "center", "any", "outer", "random",
+];
+
 /// Main configuration structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// General settings
     #[serde(default)]
@@ -49,13 +58,33 @@ pub struct Config {
     #[serde(default)]
     pub awww: AwwwConfig,
 
+    /// Wallpaper backend selection
+    #[serde(default)]
+    pub backend: BackendConfig,
+
     /// Matugen-specific settings
     #[serde(default)]
     pub matugen: MatugenConfig,
+
+    /// Theme generator selection
+    #[serde(default)]
+    pub theme: ThemeConfig,
+
+    /// Apply pipeline settings
+    #[serde(default)]
+    pub executor: ExecutorConfig,
+
+    /// Wallpaper cycling settings
+    #[serde(default)]
+    pub cycling: CyclingConfig,
+
+    /// User commands run on apply events (`[[hooks]]`)
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
 }
 
 /// General configuration options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GeneralConfig {
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
@@ -69,13 +98,38 @@ pub struct GeneralConfig {
     #[serde(default = "default_auto_detect_monitors")]
     pub auto_detect_monitors: bool,
 
-    /// Debounce delay in milliseconds for file changes
+    /// Debounce delay in milliseconds for file changes, counted from the last change
     #[serde(default = "default_debounce_ms")]
     pub debounce_ms: u64,
+
+    /// Longest a burst of file changes is held back, in milliseconds
+    #[serde(default = "default_debounce_max_wait_ms")]
+    pub debounce_max_wait_ms: u64,
+
+    /// How watched files are monitored
+    #[serde(default)]
+    pub watch_backend: WatchBackend,
+
+    /// How often the `poll` backend scans, and `auto` checks inotify, in milliseconds
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+/// File watching mechanisms
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchBackend {
+    /// inotify, switching to polling if it misses a change (e.g. on NFS)
+    #[default]
+    Auto,
+    /// inotify only
+    Inotify,
+    /// Periodic scans of the watched directories
+    Poll,
 }
 
 /// DMS configuration options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DmsConfig {
     /// Path to DMS session.json file
     #[serde(default = "default_session_file")]
@@ -88,10 +142,14 @@ pub struct DmsConfig {
     /// Path to DMS cache directory
     #[serde(default = "default_cache_dir")]
     pub cache_dir: String,
+
+    /// Size cap in MiB for converted wallpaper copies in `<cache_dir>/dms-awww` (0 disables)
+    #[serde(default = "default_image_cache_max_mb")]
+    pub image_cache_max_mb: u64,
 }
 
 /// Niri configuration options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct NiriConfig {
     /// Explicitly defined outputs (overrides auto-detection)
     #[serde(default)]
@@ -99,19 +157,193 @@ pub struct NiriConfig {
 }
 
 /// Awww configuration options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AwwwConfig {
     /// Enable awww integration
     #[serde(default = "default_enabled")]
     pub enabled: bool,
 
-    /// Additional arguments to pass to awww
+    /// Additional global arguments to pass to awww (before `img`)
     #[serde(default)]
     pub extra_args: Vec<String>,
+
+    /// Additional arguments to pass to `awww img`
+    #[serde(default)]
+    pub img_args: Vec<String>,
+
+    /// Skip outputs whose `awww query` state already matches the wallpaper
+    #[serde(default = "default_enabled")]
+    pub skip_unchanged: bool,
+
+    /// Default transition for `awww img`
+    #[serde(default)]
+    pub transition: TransitionConfig,
+
+    /// Per-monitor overrides, keyed by output name
+    #[serde(default)]
+    pub monitors: HashMap<String, AwwwOutputConfig>,
+
+    /// Per-event transition overrides
+    #[serde(default)]
+    pub events: AwwwEventsConfig,
+
+    /// awww-daemon supervision
+    #[serde(default)]
+    pub daemon: AwwwDaemonConfig,
+}
+
+/// awww-daemon supervision options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AwwwDaemonConfig {
+    /// Start awww-daemon if it is not running
+    #[serde(default)]
+    pub manage: bool,
+
+    /// Restart a managed awww-daemon when it exits
+    #[serde(default = "default_enabled")]
+    pub restart: bool,
+
+    /// Arguments passed to awww-daemon when starting it
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// How often to probe the daemon, in milliseconds
+    #[serde(default = "default_probe_interval_ms")]
+    pub probe_interval_ms: u64,
+
+    /// How long to wait for the daemon to become ready, in milliseconds
+    #[serde(default = "default_ready_timeout_ms")]
+    pub ready_timeout_ms: u64,
+}
+
+/// Transition options for `awww img`
+///
+/// Unset fields are left to awww's own defaults (or to a less specific
+/// profile when merged).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransitionConfig {
+    /// Transition type (`--transition-type`)
+    #[serde(rename = "type", default)]
+    pub transition_type: Option<String>,
+
+    /// Duration in seconds (`--transition-duration`)
+    #[serde(default)]
+    pub duration: Option<f32>,
+
+    /// Frame rate (`--transition-fps`)
+    #[serde(default)]
+    pub fps: Option<u32>,
+
+    /// Angle in degrees for wipe/wave (`--transition-angle`)
+    #[serde(default)]
+    pub angle: Option<f32>,
+
+    /// Origin for grow/outer (`--transition-pos`)
+    #[serde(default)]
+    pub position: Option<String>,
+
+    /// Bezier curve (`--transition-bezier`)
+    #[serde(default)]
+    pub bezier: Option<String>,
+
+    /// Wave width,height (`--transition-wave`)
+    #[serde(default)]
+    pub wave: Option<String>,
+}
+
+/// Per-monitor awww overrides
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AwwwOutputConfig {
+    /// Transition fields overriding `[awww.transition]`
+    #[serde(default)]
+    pub transition: TransitionConfig,
+
+    /// Extra `awww img` arguments appended after the global `img_args`
+    #[serde(default)]
+    pub img_args: Vec<String>,
+}
+
+/// Transition overrides for specific apply events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AwwwEventsConfig {
+    /// Used when restoring the wallpaper at daemon startup
+    #[serde(default = "default_startup_transition")]
+    pub startup: TransitionConfig,
+
+    /// Used when only the light/dark mode changed
+    #[serde(default = "default_mode_change_transition")]
+    pub mode_change: TransitionConfig,
+}
+
+/// Wallpaper backend configuration options
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackendConfig {
+    /// Program that puts wallpapers on outputs
+    #[serde(default)]
+    pub kind: BackendKind,
+
+    /// swaybg backend options
+    #[serde(default)]
+    pub swaybg: SwaybgConfig,
+
+    /// hyprpaper backend options
+    #[serde(default)]
+    pub hyprpaper: HyprpaperConfig,
+
+    /// wpaperd backend options
+    #[serde(default)]
+    pub wpaperd: WpaperdConfig,
+}
+
+/// Supported wallpaper backends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// awww (configured in `[awww]`)
+    #[default]
+    Awww,
+    /// swaybg, one managed process per output
+    Swaybg,
+    /// hyprpaper via `hyprctl hyprpaper`
+    Hyprpaper,
+    /// wpaperd via its config file
+    Wpaperd,
+}
+
+/// swaybg backend options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwaybgConfig {
+    /// Scaling mode passed to `swaybg -m`
+    #[serde(default = "default_swaybg_mode")]
+    pub mode: String,
+}
+
+/// hyprpaper backend options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HyprpaperConfig {
+    /// Unload images no longer displayed after each change
+    #[serde(default = "default_enabled")]
+    pub unload_unused: bool,
+
+    /// Fit mode prefix (e.g. `contain`, `tile`)
+    #[serde(default)]
+    pub fit_mode: Option<String>,
+}
+
+/// wpaperd backend options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WpaperdConfig {
+    /// wpaperd config file rewritten on each change
+    #[serde(default = "default_wpaperd_config_file")]
+    pub config_file: String,
+
+    /// Display mode written to each section (e.g. `center`, `fit`)
+    #[serde(default)]
+    pub mode: Option<String>,
 }
 
 /// Matugen configuration options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MatugenConfig {
     /// Enable matugen integration
     #[serde(default = "default_enabled")]
@@ -124,6 +356,249 @@ pub struct MatugenConfig {
     /// Quickshell directory
     #[serde(default = "default_shell_dir")]
     pub shell_dir: String,
+
+    /// Which wallpaper the theme is generated from with per-monitor wallpapers
+    #[serde(default)]
+    pub source: ThemeSourcePolicy,
+}
+
+/// Wallpaper the theme is generated from when outputs show different wallpapers
+///
+/// Written as `primary`, `focused`, `composite` or `output:<name>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ThemeSourcePolicy {
+    /// The first `[niri] outputs` entry, else the output at the top left of the layout
+    #[default]
+    Primary,
+    /// The output niri reports as focused
+    Focused,
+    /// A mosaic of every output's wallpaper
+    Composite,
+    /// The named output
+    Output(String),
+}
+
+impl TryFrom<String> for ThemeSourcePolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        match value.as_str() {
+            "primary" => Ok(ThemeSourcePolicy::Primary),
+            "focused" => Ok(ThemeSourcePolicy::Focused),
+            "composite" => Ok(ThemeSourcePolicy::Composite),
+            _ => match value.strip_prefix("output:") {
+                Some(name) if !name.is_empty() => Ok(ThemeSourcePolicy::Output(name.to_string())),
+                _ => Err(format!(
+                    "unknown theme source `{value}`; expected primary, focused, composite or output:<name>"
+                )),
+            },
+        }
+    }
+}
+
+impl From<ThemeSourcePolicy> for String {
+    fn from(policy: ThemeSourcePolicy) -> Self {
+        match policy {
+            ThemeSourcePolicy::Primary => "primary".to_string(),
+            ThemeSourcePolicy::Focused => "focused".to_string(),
+            ThemeSourcePolicy::Composite => "composite".to_string(),
+            ThemeSourcePolicy::Output(name) => format!("output:{name}"),
+        }
+    }
+}
+
+/// Theme generator configuration options
+///
+/// `[matugen] enabled` still switches the theme stage on or off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    /// Program that generates the color theme
+    #[serde(default)]
+    pub kind: ThemeKind,
+
+    /// Direct matugen options
+    #[serde(default)]
+    pub matugen: MatugenCliConfig,
+
+    /// wallust options
+    #[serde(default)]
+    pub wallust: WallustConfig,
+
+    /// pywal options
+    #[serde(default)]
+    pub pywal: PywalConfig,
+}
+
+/// Supported theme generators
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeKind {
+    /// `dms matugen queue` (configured in `[matugen]`)
+    #[default]
+    Dms,
+    /// `matugen image` / `matugen color` without DMS
+    Matugen,
+    /// `wallust run`
+    Wallust,
+    /// pywal (`wal -i`)
+    Pywal,
+}
+
+/// Direct matugen options
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MatugenCliConfig {
+    /// matugen config file (`--config`); matugen's default if unset
+    #[serde(default)]
+    pub config_file: Option<String>,
+
+    /// Additional arguments appended to the matugen command
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+/// wallust options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WallustConfig {
+    /// Palette used in dark mode
+    #[serde(default = "default_wallust_dark_palette")]
+    pub dark_palette: String,
+
+    /// Palette used in light mode
+    #[serde(default = "default_wallust_light_palette")]
+    pub light_palette: String,
+
+    /// Color sampling backend (`--backend`)
+    #[serde(default)]
+    pub backend: Option<String>,
+
+    /// Additional arguments appended to `wallust run`
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+/// pywal options
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PywalConfig {
+    /// Color extraction backend (`--backend`)
+    #[serde(default)]
+    pub backend: Option<String>,
+
+    /// Additional arguments appended to `wal`
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+/// Apply pipeline options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutorConfig {
+    /// Ordering between the awww and matugen stages
+    #[serde(default)]
+    pub order: ApplyOrder,
+
+    /// Delay between the first and second stage, in milliseconds
+    /// (ignored for `parallel`)
+    #[serde(default)]
+    pub stage_delay_ms: u64,
+
+    /// Timeout for the awww stage in milliseconds (0 disables)
+    #[serde(default = "default_awww_timeout_ms")]
+    pub awww_timeout_ms: u64,
+
+    /// Timeout for the matugen stage in milliseconds (0 disables)
+    #[serde(default = "default_matugen_timeout_ms")]
+    pub matugen_timeout_ms: u64,
+
+    /// Retry policy for recoverable command failures
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// Retry policy with exponential backoff and jitter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Total attempts per command, including the first (1 disables retries)
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+
+    /// Delay before the first retry, in milliseconds
+    #[serde(default = "default_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound for the delay between attempts, in milliseconds
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Factor applied to the delay after each attempt
+    #[serde(default = "default_retry_multiplier")]
+    pub multiplier: f64,
+
+    /// Random spread applied to each delay, as a fraction (0.2 = ±20%)
+    #[serde(default = "default_retry_jitter")]
+    pub jitter: f64,
+}
+
+/// Wallpaper cycling options
+///
+/// The schedule itself comes from the DMS cycling settings in session.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CyclingConfig {
+    /// Run DMS wallpaper cycling (for when DMS built-in wallpapers are disabled)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// A user command run on an apply event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookConfig {
+    /// Event that triggers the hook
+    pub on: HookEvent,
+
+    /// Shell command, run with `sh -c`
+    pub command: String,
+
+    /// Timeout in milliseconds (0 disables)
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Events hooks can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    /// The wallpaper stage succeeded
+    AfterAwww,
+    /// The theme stage succeeded
+    AfterMatugen,
+    /// Any stage failed
+    OnError,
+    /// An apply caused by a light/dark flip finished
+    OnModeChange,
+}
+
+impl HookEvent {
+    /// Name as written in the config file
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::AfterAwww => "after-awww",
+            HookEvent::AfterMatugen => "after-matugen",
+            HookEvent::OnError => "on-error",
+            HookEvent::OnModeChange => "on-mode-change",
+        }
+    }
+}
+
+/// Ordering between the awww and matugen stages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApplyOrder {
+    /// Run both stages at the same time
+    Parallel,
+    /// Run matugen after awww finishes (avoids theme/wallpaper flicker)
+    #[default]
+    AwwwFirst,
+    /// Run awww after matugen finishes
+    MatugenFirst,
 }
 
 fn default_log_level() -> String {
@@ -142,6 +617,14 @@ fn default_debounce_ms() -> u64 {
     100
 }
 
+fn default_debounce_max_wait_ms() -> u64 {
+    1000
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
 fn default_session_file() -> String {
     DEFAULT_SESSION_FILE.to_string()
 }
@@ -154,10 +637,84 @@ fn default_cache_dir() -> String {
     DEFAULT_CACHE_DIR.to_string()
 }
 
+fn default_image_cache_max_mb() -> u64 {
+    512
+}
+
 fn default_enabled() -> bool {
     true
 }
 
+fn default_awww_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_matugen_timeout_ms() -> u64 {
+    120_000
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_initial_backoff_ms() -> u64 {
+    200
+}
+
+fn default_retry_max_backoff_ms() -> u64 {
+    5000
+}
+
+fn default_retry_multiplier() -> f64 {
+    2.0
+}
+
+fn default_retry_jitter() -> f64 {
+    0.2
+}
+
+fn default_probe_interval_ms() -> u64 {
+    2000
+}
+
+fn default_ready_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_startup_transition() -> TransitionConfig {
+    TransitionConfig {
+        transition_type: Some("none".to_string()),
+        ..TransitionConfig::default()
+    }
+}
+
+fn default_mode_change_transition() -> TransitionConfig {
+    TransitionConfig {
+        transition_type: Some("fade".to_string()),
+        ..TransitionConfig::default()
+    }
+}
+
+fn default_swaybg_mode() -> String {
+    "fill".to_string()
+}
+
+fn default_wpaperd_config_file() -> String {
+    DEFAULT_WPAPERD_CONFIG_FILE.to_string()
+}
+
+fn default_wallust_dark_palette() -> String {
+    "dark16".to_string()
+}
+
+fn default_wallust_light_palette() -> String {
+    "light16".to_string()
+}
+
 fn default_matugen_scheme() -> String {
     DEFAULT_MATUGEN_SCHEME.to_string()
 }
@@ -173,6 +730,9 @@ impl Default for GeneralConfig {
             log_file: default_log_file(),
             auto_detect_monitors: default_auto_detect_monitors(),
             debounce_ms: default_debounce_ms(),
+            debounce_max_wait_ms: default_debounce_max_wait_ms(),
+            watch_backend: WatchBackend::default(),
+            poll_interval_ms: default_poll_interval_ms(),
         }
     }
 }
@@ -183,14 +743,50 @@ impl Default for DmsConfig {
             session_file: default_session_file(),
             settings_file: default_settings_file(),
             cache_dir: default_cache_dir(),
+            image_cache_max_mb: default_image_cache_max_mb(),
+        }
+    }
+}
+
+impl Default for SwaybgConfig {
+    fn default() -> Self {
+        Self {
+            mode: default_swaybg_mode(),
+        }
+    }
+}
+
+impl Default for HyprpaperConfig {
+    fn default() -> Self {
+        Self {
+            unload_unused: true,
+            fit_mode: None,
         }
     }
 }
 
-impl Default for NiriConfig {
+impl Default for WpaperdConfig {
     fn default() -> Self {
         Self {
-            outputs: Vec::new(),
+            config_file: default_wpaperd_config_file(),
+            mode: None,
+        }
+    }
+}
+
+impl Default for CyclingConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Default for WallustConfig {
+    fn default() -> Self {
+        Self {
+            dark_palette: default_wallust_dark_palette(),
+            light_palette: default_wallust_light_palette(),
+            backend: None,
+            extra_args: Vec::new(),
         }
     }
 }
@@ -200,28 +796,170 @@ impl Default for AwwwConfig {
         Self {
             enabled: default_enabled(),
             extra_args: Vec::new(),
+            img_args: Vec::new(),
+            skip_unchanged: default_enabled(),
+            transition: TransitionConfig::default(),
+            monitors: HashMap::new(),
+            events: AwwwEventsConfig::default(),
+            daemon: AwwwDaemonConfig::default(),
         }
     }
 }
 
-impl Default for MatugenConfig {
+impl Default for ExecutorConfig {
     fn default() -> Self {
         Self {
-            enabled: default_enabled(),
-            default_scheme: default_matugen_scheme(),
-            shell_dir: default_shell_dir(),
+            order: ApplyOrder::default(),
+            stage_delay_ms: 0,
+            awww_timeout_ms: default_awww_timeout_ms(),
+            matugen_timeout_ms: default_matugen_timeout_ms(),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_retry_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+            multiplier: default_retry_multiplier(),
+            jitter: default_retry_jitter(),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before retrying after failed attempt number `attempt` (1-based)
+    ///
+    /// `sample` is a random value in `[0, 1)` used for jitter.
+    pub fn backoff(&self, attempt: u32, sample: f64) -> std::time::Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let base = (self.initial_backoff_ms as f64 * exp).min(self.max_backoff_ms as f64);
+        let spread = 1.0 + self.jitter * (2.0 * sample - 1.0);
+        std::time::Duration::from_millis((base * spread).max(0.0) as u64)
+    }
+}
+
+impl Default for AwwwDaemonConfig {
+    fn default() -> Self {
+        Self {
+            manage: false,
+            restart: default_enabled(),
+            args: Vec::new(),
+            probe_interval_ms: default_probe_interval_ms(),
+            ready_timeout_ms: default_ready_timeout_ms(),
         }
     }
 }
 
-impl Default for Config {
+impl Default for AwwwEventsConfig {
     fn default() -> Self {
         Self {
-            general: GeneralConfig::default(),
-            dms: DmsConfig::default(),
-            niri: NiriConfig::default(),
-            awww: AwwwConfig::default(),
-            matugen: MatugenConfig::default(),
+            startup: default_startup_transition(),
+            mode_change: default_mode_change_transition(),
+        }
+    }
+}
+
+impl TransitionConfig {
+    /// Overlay `other` on top of this profile; fields set in `other` win
+    pub fn merged(&self, other: &TransitionConfig) -> TransitionConfig {
+        TransitionConfig {
+            transition_type: other.transition_type.clone().or_else(|| self.transition_type.clone()),
+            duration: other.duration.or(self.duration),
+            fps: other.fps.or(self.fps),
+            angle: other.angle.or(self.angle),
+            position: other.position.clone().or_else(|| self.position.clone()),
+            bezier: other.bezier.clone().or_else(|| self.bezier.clone()),
+            wave: other.wave.clone().or_else(|| self.wave.clone()),
+        }
+    }
+
+    /// Convert to `awww img` command-line flags
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(t) = &self.transition_type {
+            args.extend(["--transition-type".to_string(), t.clone()]);
+        }
+        if let Some(d) = self.duration {
+            args.extend(["--transition-duration".to_string(), d.to_string()]);
+        }
+        if let Some(fps) = self.fps {
+            args.extend(["--transition-fps".to_string(), fps.to_string()]);
+        }
+        if let Some(angle) = self.angle {
+            args.extend(["--transition-angle".to_string(), angle.to_string()]);
+        }
+        if let Some(pos) = &self.position {
+            args.extend(["--transition-pos".to_string(), pos.clone()]);
+        }
+        if let Some(bezier) = &self.bezier {
+            args.extend(["--transition-bezier".to_string(), bezier.clone()]);
+        }
+        if let Some(wave) = &self.wave {
+            args.extend(["--transition-wave".to_string(), wave.clone()]);
+        }
+
+        args
+    }
+
+    /// Validate field values, using `key` as the config path in errors
+    fn validate(&self, key: &str) -> Result<()> {
+        if let Some(t) = &self.transition_type {
+            if !AWWW_TRANSITION_TYPES.contains(&t.as_str()) {
+                return Err(DmsAwwwError::InvalidConfig {
+                    key: format!("{key}.type"),
+                    reason: format!("unknown transition type: {t}"),
+                });
+            }
+        }
+        if let Some(d) = self.duration {
+            if !d.is_finite() || d < 0.0 {
+                return Err(DmsAwwwError::InvalidConfig {
+                    key: format!("{key}.duration"),
+                    reason: format!("must be a non-negative number of seconds, got {d}"),
+                });
+            }
+        }
+        if self.fps == Some(0) {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: format!("{key}.fps"),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl AwwwConfig {
+    /// Transition for a monitor: `[awww.transition]` with that monitor's overrides applied
+    pub fn transition_for(&self, monitor: &str) -> TransitionConfig {
+        match self.monitors.get(monitor) {
+            Some(output) => self.transition.merged(&output.transition),
+            None => self.transition.clone(),
+        }
+    }
+
+    /// `awww img` arguments for a monitor: global `img_args` then the monitor's own
+    pub fn img_args_for(&self, monitor: &str) -> Vec<String> {
+        let mut args = self.img_args.clone();
+        if let Some(output) = self.monitors.get(monitor) {
+            args.extend(output.img_args.iter().cloned());
+        }
+        args
+    }
+}
+
+impl Default for MatugenConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            default_scheme: default_matugen_scheme(),
+            shell_dir: default_shell_dir(),
+            source: ThemeSourcePolicy::default(),
         }
     }
 }
@@ -229,47 +967,36 @@ impl Default for Config {
 impl Config {
     /// Load configuration from file with environment variable overrides
     pub fn load() -> Result<Self> {
-        let mut settings = config::Config::builder();
+        Self::load_from(None)
+    }
 
-        // Check for config files in order of priority
-        let config_paths = vec![
-            Self::xdg_config_home("dms-awww/config.toml"),
-            Self::xdg_config_home("dms-awww/config.yaml"),
-            Self::xdg_config_home("dms-awww/config.yml"),
-            PathBuf::from("/etc/dms-awww/config.toml"),
-        ];
+    /// Load configuration from `path`, or from the first config file found
+    ///
+    /// A given `path` must exist. Files without a `.yaml`/`.yml` extension are
+    /// read as TOML.
+    pub fn load_from(path: Option<&Path>) -> Result<Self> {
+        let mut settings = config::Config::builder();
 
-        let mut found_config = false;
-        for path in config_paths {
-            if path.exists() {
+        let file = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => Self::config_file_candidates().into_iter().find(|p| p.exists()),
+        };
+        match file {
+            Some(path) => {
                 tracing::debug!("Loading configuration from: {}", path.display());
-                let source = match path.extension().and_then(|e| e.to_str()) {
-                    Some("toml") => {
-                        config::File::from(path.as_path())
-                            .format(config::FileFormat::Toml)
-                    }
-                    Some("yaml" | "yml") => {
-                        config::File::from(path.as_path())
-                            .format(config::FileFormat::Yaml)
-                    }
-                    _ => continue,
+                let format = match path.extension().and_then(|e| e.to_str()) {
+                    Some("yaml" | "yml") => config::FileFormat::Yaml,
+                    _ => config::FileFormat::Toml,
                 };
-
-                settings = settings.add_source(source);
-                found_config = true;
-                break;
+                settings = settings.add_source(config::File::from(path.as_path()).format(format));
             }
-        }
-
-        if !found_config {
-            tracing::debug!("No configuration file found, using defaults");
+            None => tracing::debug!("No configuration file found, using defaults"),
         }
 
         // Build base configuration
         let mut config: Config = settings
             .build()?
-            .try_deserialize()
-            .unwrap_or_else(|_| Config::default());
+            .try_deserialize()?;
 
         // Apply environment variable overrides
         config.apply_env_overrides();
@@ -280,6 +1007,29 @@ impl Config {
         Ok(config)
     }
 
+    /// Config file locations, in order of priority
+    fn config_file_candidates() -> Vec<PathBuf> {
+        vec![
+            Self::xdg_config_home("dms-awww/config.toml"),
+            Self::xdg_config_home("dms-awww/config.yaml"),
+            Self::xdg_config_home("dms-awww/config.yml"),
+            PathBuf::from("/etc/dms-awww/config.toml"),
+        ]
+    }
+
+    /// Config file `load_from(path)` reads, or where a new one would be picked up
+    pub fn config_file_path(path: Option<&Path>) -> PathBuf {
+        if let Some(path) = path {
+            return path.to_path_buf();
+        }
+        let candidates = Self::config_file_candidates();
+        candidates
+            .iter()
+            .find(|p| p.exists())
+            .unwrap_or(&candidates[0])
+            .clone()
+    }
+
     /// Get XDG config home path
     fn xdg_config_home(path: &str) -> PathBuf {
         let xdg = env::var("XDG_CONFIG_HOME")
@@ -304,6 +1054,14 @@ impl Config {
         if let Some(val) = get_env("AUTO_DETECT_MONITORS") {
             self.general.auto_detect_monitors = val.parse().unwrap_or(self.general.auto_detect_monitors);
         }
+        if let Some(backend) = get_env("WATCH_BACKEND") {
+            match backend.to_lowercase().as_str() {
+                "auto" => self.general.watch_backend = WatchBackend::Auto,
+                "inotify" => self.general.watch_backend = WatchBackend::Inotify,
+                "poll" => self.general.watch_backend = WatchBackend::Poll,
+                _ => tracing::warn!("Ignoring unknown DMS_AWWW_WATCH_BACKEND: {}", backend),
+            }
+        }
 
         // DMS overrides
         if let Some(path) = get_env("SESSION_FILE") {
@@ -315,6 +1073,9 @@ impl Config {
         if let Some(path) = get_env("CACHE_DIR") {
             self.dms.cache_dir = path;
         }
+        if let Some(val) = get_env("IMAGE_CACHE_MAX_MB") {
+            self.dms.image_cache_max_mb = val.parse().unwrap_or(self.dms.image_cache_max_mb);
+        }
 
         // Niri overrides
         if let Some(outputs) = get_env("NIRI_OUTPUTS") {
@@ -329,6 +1090,28 @@ impl Config {
         if let Some(val) = get_env("AWWW_ENABLED") {
             self.awww.enabled = val.parse().unwrap_or(self.awww.enabled);
         }
+        if let Some(val) = get_env("AWWW_MANAGE_DAEMON") {
+            self.awww.daemon.manage = val.parse().unwrap_or(self.awww.daemon.manage);
+        }
+        if let Some(kind) = get_env("TRANSITION_TYPE") {
+            self.awww.transition.transition_type = Some(kind);
+        }
+
+        // Backend overrides
+        if let Some(kind) = get_env("BACKEND") {
+            match kind.to_lowercase().as_str() {
+                "awww" => self.backend.kind = BackendKind::Awww,
+                "swaybg" => self.backend.kind = BackendKind::Swaybg,
+                "hyprpaper" => self.backend.kind = BackendKind::Hyprpaper,
+                "wpaperd" => self.backend.kind = BackendKind::Wpaperd,
+                _ => tracing::warn!("Ignoring unknown DMS_AWWW_BACKEND: {}", kind),
+            }
+        }
+
+        // Cycling overrides
+        if let Some(val) = get_env("CYCLING_ENABLED") {
+            self.cycling.enabled = val.parse().unwrap_or(self.cycling.enabled);
+        }
 
         // Matugen overrides
         if let Some(val) = get_env("MATUGEN_ENABLED") {
@@ -340,6 +1123,23 @@ impl Config {
         if let Some(dir) = get_env("SHELL_DIR") {
             self.matugen.shell_dir = dir;
         }
+        if let Some(source) = get_env("MATUGEN_SOURCE") {
+            match ThemeSourcePolicy::try_from(source) {
+                Ok(policy) => self.matugen.source = policy,
+                Err(e) => tracing::warn!("Ignoring DMS_AWWW_MATUGEN_SOURCE: {}", e),
+            }
+        }
+
+        // Theme overrides
+        if let Some(kind) = get_env("THEME") {
+            match kind.to_lowercase().as_str() {
+                "dms" => self.theme.kind = ThemeKind::Dms,
+                "matugen" => self.theme.kind = ThemeKind::Matugen,
+                "wallust" => self.theme.kind = ThemeKind::Wallust,
+                "pywal" => self.theme.kind = ThemeKind::Pywal,
+                _ => tracing::warn!("Ignoring unknown DMS_AWWW_THEME: {}", kind),
+            }
+        }
     }
 
     /// Expand ~ and environment variables in paths
@@ -349,6 +1149,10 @@ impl Config {
         self.dms.cache_dir = Self::expand_path(&self.dms.cache_dir);
         self.general.log_file = Self::expand_path(&self.general.log_file);
         self.matugen.shell_dir = Self::expand_path(&self.matugen.shell_dir);
+        self.backend.wpaperd.config_file = Self::expand_path(&self.backend.wpaperd.config_file);
+        if let Some(file) = &self.theme.matugen.config_file {
+            self.theme.matugen.config_file = Some(Self::expand_path(file));
+        }
     }
 
     /// Expand a single path (handles ~ and $VAR)
@@ -403,6 +1207,65 @@ impl Config {
             }
         }
 
+        // Validate awww transition profiles
+        self.awww.transition.validate("awww.transition")?;
+        for (monitor, output) in &self.awww.monitors {
+            output.transition.validate(&format!("awww.monitors.{monitor}.transition"))?;
+        }
+        self.awww.events.startup.validate("awww.events.startup")?;
+        self.awww.events.mode_change.validate("awww.events.mode_change")?;
+
+        // Validate hooks
+        for (i, hook) in self.hooks.iter().enumerate() {
+            if hook.command.trim().is_empty() {
+                return Err(DmsAwwwError::InvalidConfig {
+                    key: format!("hooks[{i}].command"),
+                    reason: "must not be empty".to_string(),
+                });
+            }
+        }
+
+        let retry = &self.executor.retry;
+        if retry.attempts == 0 {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "executor.retry.attempts".to_string(),
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "executor.retry.jitter".to_string(),
+                reason: format!("must be between 0 and 1, got {}", retry.jitter),
+            });
+        }
+        if !retry.multiplier.is_finite() || retry.multiplier < 1.0 {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "executor.retry.multiplier".to_string(),
+                reason: format!("must be at least 1, got {}", retry.multiplier),
+            });
+        }
+
+        if self.matugen.source == ThemeSourcePolicy::Output(String::new()) {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "matugen.source".to_string(),
+                reason: "output:<name> needs an output name".to_string(),
+            });
+        }
+
+        if self.general.poll_interval_ms == 0 {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "general.poll_interval_ms".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
+        if self.awww.daemon.probe_interval_ms == 0 {
+            return Err(DmsAwwwError::InvalidConfig {
+                key: "awww.daemon.probe_interval_ms".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
         Ok(())
     }
 
@@ -421,6 +1284,11 @@ impl Config {
         PathBuf::from(&self.dms.cache_dir)
     }
 
+    /// Directory for dms-awww's converted wallpaper copies, inside the DMS cache dir
+    pub fn image_cache_path(&self) -> PathBuf {
+        self.cache_dir_path().join("dms-awww")
+    }
+
     /// Check if awww is enabled
     pub fn awww_enabled(&self) -> bool {
         self.awww.enabled
@@ -438,6 +1306,20 @@ impl Config {
         }
         Vec::new() // Will trigger auto-detection
     }
+
+    /// Whether moving from this config to `new` changes what is on screen
+    ///
+    /// True when the monitors, the DMS files, the wallpaper backend or the
+    /// theme generator differ; a reload then re-applies the wallpaper at once.
+    pub fn needs_reapply(&self, new: &Config) -> bool {
+        self.general.auto_detect_monitors != new.general.auto_detect_monitors
+            || self.niri != new.niri
+            || self.dms != new.dms
+            || self.awww != new.awww
+            || self.backend != new.backend
+            || self.matugen != new.matugen
+            || self.theme != new.theme
+    }
 }
 
 #[cfg(test)]
@@ -448,7 +1330,7 @@ mod tests {
     fn test_default_config() {
         let config = Config::default();
         assert_eq!(config.general.log_level, "info");
-        assert_eq!(config.general.auto_detect_monitors, true);
+        assert!(config.general.auto_detect_monitors);
         assert!(config.awww.enabled);
         assert!(config.matugen.enabled);
     }
@@ -478,4 +1360,93 @@ mod tests {
         invalid_config.general.log_level = "invalid".to_string();
         assert!(invalid_config.validate().is_err());
     }
+
+    #[test]
+    fn test_default_executor_order() {
+        let config = Config::default();
+        assert_eq!(config.executor.order, ApplyOrder::AwwwFirst);
+        assert_eq!(config.executor.stage_delay_ms, 0);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = RetryConfig {
+            attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            multiplier: 2.0,
+            jitter: 0.5,
+        };
+
+        // sample 0.5 means no jitter
+        assert_eq!(retry.backoff(1, 0.5).as_millis(), 100);
+        assert_eq!(retry.backoff(2, 0.5).as_millis(), 200);
+        assert_eq!(retry.backoff(3, 0.5).as_millis(), 400);
+        assert_eq!(retry.backoff(5, 0.5).as_millis(), 1000);
+
+        // jitter spreads around the base delay
+        assert_eq!(retry.backoff(1, 0.0).as_millis(), 50);
+        assert_eq!(retry.backoff(1, 1.0).as_millis(), 150);
+    }
+
+    #[test]
+    fn test_transition_merge_and_args() {
+        let base = TransitionConfig {
+            transition_type: Some("grow".to_string()),
+            duration: Some(2.0),
+            fps: Some(60),
+            position: Some("center".to_string()),
+            ..TransitionConfig::default()
+        };
+        let over = TransitionConfig {
+            transition_type: Some("wipe".to_string()),
+            angle: Some(30.0),
+            ..TransitionConfig::default()
+        };
+
+        let args = base.merged(&over).to_args();
+        assert_eq!(
+            args,
+            vec![
+                "--transition-type", "wipe",
+                "--transition-duration", "2",
+                "--transition-fps", "60",
+                "--transition-angle", "30",
+                "--transition-pos", "center",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_transition_type() {
+        let mut config = Config::default();
+        config.awww.transition.transition_type = Some("sparkle".to_string());
+        assert!(config.validate().is_err());
+
+        config.awww.transition.transition_type = Some("fade".to_string());
+        config.awww.transition.fps = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_needs_reapply() {
+        let old = Config::default();
+
+        let mut new = old.clone();
+        new.general.log_level = "debug".to_string();
+        new.cycling.enabled = !old.cycling.enabled;
+        assert!(!old.needs_reapply(&new));
+
+        let mut new = old.clone();
+        new.niri.outputs = vec!["DP-1".to_string()];
+        assert!(old.needs_reapply(&new));
+
+        let mut new = old.clone();
+        new.backend.kind = BackendKind::Swaybg;
+        assert!(old.needs_reapply(&new));
+
+        let mut new = old.clone();
+        new.awww.transition.fps = Some(30);
+        assert!(old.needs_reapply(&new));
+    }
 }
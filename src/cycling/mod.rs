@@ -0,0 +1,390 @@
+//! Wallpaper cycling
+//!
+//! DMS stores its cycling (slideshow) settings in session.json, but only runs
+//! them when its built-in wallpapers are enabled. This module reads those
+//! settings and schedules the next image per target (`ALL` or a monitor).
+//! Picked images are kept as overrides on top of the DMS state until DMS
+//! itself assigns a different wallpaper to that target.
+
+use crate::dms::{SessionJson, Wallpaper, WallpaperChange};
+use crate::image::ImageFormat;
+use chrono::{Local, NaiveTime};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::time::{Duration, Instant};
+
+/// When the next image is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every `Duration`
+    Interval(Duration),
+    /// Once a day at the given local time
+    TimeOfDay(NaiveTime),
+}
+
+impl Schedule {
+    /// Parse DMS cycling fields (`mode` is `interval` or `time`)
+    fn parse(mode: Option<&str>, interval_secs: Option<u64>, time: Option<&str>) -> Option<Self> {
+        match mode.unwrap_or("interval") {
+            "time" => {
+                let time = NaiveTime::parse_from_str(time?, "%H:%M").ok()?;
+                Some(Schedule::TimeOfDay(time))
+            }
+            _ => {
+                let secs = interval_secs.filter(|s| *s > 0)?;
+                Some(Schedule::Interval(Duration::from_secs(secs)))
+            }
+        }
+    }
+
+    /// Time from now until the next tick
+    fn until_next(&self) -> Duration {
+        match self {
+            Schedule::Interval(interval) => *interval,
+            Schedule::TimeOfDay(time) => {
+                let now = Local::now().naive_local();
+                let mut next = now.date().and_time(*time);
+                if next <= now {
+                    next += chrono::Duration::days(1);
+                }
+                (next - now).to_std().unwrap_or(Duration::from_secs(60))
+            }
+        }
+    }
+}
+
+/// Cycling settings for one target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclingSettings {
+    /// When to advance
+    pub schedule: Schedule,
+    /// Directory to cycle through; the current wallpaper's directory if unset
+    pub directory: Option<PathBuf>,
+}
+
+/// Read the enabled cycling targets from session.json
+///
+/// In per-monitor mode each monitor uses its own `monitorCyclingSettings`
+/// entry; otherwise the global settings apply to `ALL`.
+pub fn settings_from_session(session: &SessionJson) -> BTreeMap<String, CyclingSettings> {
+    let mut targets = BTreeMap::new();
+
+    if session.per_monitor_wallpaper.unwrap_or(false) {
+        for (monitor, cycling) in &session.monitor_cycling_settings {
+            if !cycling.enabled.unwrap_or(false) {
+                continue;
+            }
+            let schedule = Schedule::parse(cycling.mode.as_deref(), cycling.interval, cycling.time.as_deref());
+            if let Some(schedule) = schedule {
+                let directory = cycling.directory.as_ref().map(PathBuf::from);
+                targets.insert(monitor.clone(), CyclingSettings { schedule, directory });
+            }
+        }
+    } else if session.wallpaper_cycling_enabled.unwrap_or(false) {
+        let schedule = Schedule::parse(
+            session.wallpaper_cycling_mode.as_deref(),
+            session.wallpaper_cycling_interval,
+            session.wallpaper_cycling_time.as_deref(),
+        );
+        if let Some(schedule) = schedule {
+            let directory = session.wallpaper_cycling_directory.as_ref().map(PathBuf::from);
+            targets.insert("ALL".to_string(), CyclingSettings { schedule, directory });
+        }
+    }
+
+    targets
+}
+
+/// Pick the image after `current` in `directory`, wrapping around
+///
+/// Files are ordered by name. Returns None if there is no other image.
+pub fn next_image(directory: &Path, current: &str) -> Option<PathBuf> {
+    let mut images: Vec<PathBuf> = fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_image(path))
+        .collect();
+    images.sort();
+
+    let position = images.iter().position(|p| p == Path::new(current));
+    let next = match position {
+        Some(i) => images.get((i + 1) % images.len())?,
+        None => images.first()?,
+    };
+
+    (next != Path::new(current)).then(|| next.clone())
+}
+
+/// Check the file extension against the supported image formats
+fn is_image(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let extension = extension.to_ascii_lowercase();
+    ImageFormat::ALL
+        .iter()
+        .any(|format| format.extensions().contains(&extension.as_str()))
+}
+
+/// A scheduled target
+struct Target {
+    settings: CyclingSettings,
+    due: Instant,
+}
+
+/// Image picked by the cycler, shown instead of the DMS wallpaper `base`
+struct Override {
+    base: String,
+    path: String,
+}
+
+/// Cycling scheduler
+pub struct Cycler {
+    enabled: bool,
+    targets: BTreeMap<String, Target>,
+    overrides: HashMap<String, Override>,
+}
+
+impl Default for Cycler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cycler {
+    /// Create an idle cycler
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            targets: BTreeMap::new(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Create a cycler that ignores all settings (`[cycling] enabled = false`)
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::new()
+        }
+    }
+
+    /// Replace the cycling settings, keeping the schedule of unchanged targets
+    pub fn update(&mut self, settings: BTreeMap<String, CyclingSettings>) {
+        if !self.enabled {
+            return;
+        }
+        self.targets.retain(|target, t| settings.get(target) == Some(&t.settings));
+        self.overrides.retain(|target, _| settings.contains_key(target));
+
+        for (target, settings) in settings {
+            if self.targets.contains_key(&target) {
+                continue;
+            }
+            let wait = settings.schedule.until_next();
+            tracing::info!("Cycling wallpaper for {} in {}s", target, wait.as_secs());
+            self.targets.insert(target, Target { settings, due: Instant::now() + wait });
+        }
+    }
+
+    /// When the next target is due, if any
+    pub fn next_due(&self) -> Option<Instant> {
+        self.targets.values().map(|t| t.due).min()
+    }
+
+    /// Wait until the next target is due
+    ///
+    /// Never resolves while no target is scheduled.
+    pub async fn wait(&self) {
+        match self.next_due() {
+            Some(due) => tokio::time::sleep_until(due).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Targets due at `now`, rescheduled for their next tick
+    pub fn take_due(&mut self, now: Instant) -> Vec<String> {
+        let mut due = Vec::new();
+        for (target, t) in &mut self.targets {
+            if t.due <= now {
+                t.due = now + t.settings.schedule.until_next();
+                due.push(target.clone());
+            }
+        }
+        due
+    }
+
+    /// Advance `target` to the next image after what `state` shows
+    ///
+    /// `state` must already have overrides applied. Returns whether a new
+    /// image was picked.
+    pub fn advance(&mut self, state: &WallpaperChange, dms_state: &WallpaperChange, target: &str) -> bool {
+        let (Some(shown), Some(base)) = (find(state, target), find(dms_state, target)) else {
+            return false;
+        };
+        let Some(settings) = self.targets.get(target).map(|t| &t.settings) else {
+            return false;
+        };
+
+        let directory = match &settings.directory {
+            Some(dir) => dir.clone(),
+            None => match Path::new(&shown.path).parent() {
+                Some(dir) => dir.to_path_buf(),
+                None => return false,
+            },
+        };
+
+        let Some(next) = next_image(&directory, &shown.path) else {
+            tracing::debug!("No other image to cycle to in {}", directory.display());
+            return false;
+        };
+
+        tracing::info!("Cycling {} to {}", target, next.display());
+        self.overrides.insert(
+            target.to_string(),
+            Override {
+                base: base.path.clone(),
+                path: next.to_string_lossy().into_owned(),
+            },
+        );
+        true
+    }
+
+    /// Show cycled images in place of the DMS wallpapers they replaced
+    ///
+    /// Overrides are dropped once DMS assigns a different wallpaper.
+    pub fn apply_overrides(&mut self, state: &mut WallpaperChange) {
+        self.overrides.retain(|target, o| {
+            find(state, target).map(|w| w.path == o.base).unwrap_or(false)
+        });
+
+        for wallpaper in &mut state.wallpapers {
+            let target = wallpaper.monitor.as_deref().unwrap_or("ALL");
+            if let Some(o) = self.overrides.get(target) {
+                *wallpaper = match &wallpaper.monitor {
+                    Some(monitor) => Wallpaper::for_monitor(o.path.clone(), monitor.clone()),
+                    None => Wallpaper::new(o.path.clone()),
+                };
+            }
+        }
+    }
+}
+
+/// The wallpaper assigned to `target` (`ALL` for a global wallpaper)
+fn find<'a>(state: &'a WallpaperChange, target: &str) -> Option<&'a Wallpaper> {
+    state
+        .wallpapers
+        .iter()
+        .find(|w| w.monitor.as_deref().unwrap_or("ALL") == target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(json: &str) -> SessionJson {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_settings_from_session() {
+        let global = session(
+            r#"{"wallpaperPath": "/w/a.png", "wallpaperCyclingEnabled": true,
+                "wallpaperCyclingMode": "interval", "wallpaperCyclingInterval": 300}"#,
+        );
+        let targets = settings_from_session(&global);
+        assert_eq!(
+            targets.get("ALL").map(|s| s.schedule),
+            Some(Schedule::Interval(Duration::from_secs(300)))
+        );
+
+        let per_monitor = session(
+            r#"{"perMonitorWallpaper": true, "monitorCyclingSettings": {
+                "DP-1": {"enabled": true, "mode": "time", "time": "07:30", "directory": "/walls"},
+                "HDMI-A-1": {"enabled": false, "interval": 60}}}"#,
+        );
+        let targets = settings_from_session(&per_monitor);
+        assert_eq!(targets.len(), 1);
+        let dp1 = &targets["DP-1"];
+        assert_eq!(dp1.schedule, Schedule::TimeOfDay(NaiveTime::from_hms_opt(7, 30, 0).unwrap()));
+        assert_eq!(dp1.directory, Some(PathBuf::from("/walls")));
+
+        assert!(settings_from_session(&session(r#"{"wallpaperCyclingEnabled": false}"#)).is_empty());
+    }
+
+    #[test]
+    fn test_next_image_wraps() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["b.png", "a.jpg", "c.webp", "notes.txt"] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let path = |name: &str| dir.path().join(name);
+
+        assert_eq!(next_image(dir.path(), path("a.jpg").to_str().unwrap()), Some(path("b.png")));
+        assert_eq!(next_image(dir.path(), path("c.webp").to_str().unwrap()), Some(path("a.jpg")));
+        assert_eq!(next_image(dir.path(), "/elsewhere/x.png"), Some(path("a.jpg")));
+    }
+
+    #[test]
+    fn test_is_image_covers_converted_formats() {
+        for name in ["a.svg", "b.HEIC", "c.heif", "d.jxl", "e.JPG"] {
+            assert!(is_image(Path::new(name)), "{name}");
+        }
+        assert!(!is_image(Path::new("notes.txt")));
+        assert!(!is_image(Path::new("no-extension")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cycler_schedule_and_overrides() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["a.png", "b.png"] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let a = dir.path().join("a.png").to_string_lossy().into_owned();
+        let b = dir.path().join("b.png").to_string_lossy().into_owned();
+
+        let mut cycler = Cycler::new();
+        let mut settings = BTreeMap::new();
+        settings.insert(
+            "ALL".to_string(),
+            CyclingSettings {
+                schedule: Schedule::Interval(Duration::from_secs(60)),
+                directory: None,
+            },
+        );
+        cycler.update(settings);
+
+        let start = Instant::now();
+        assert_eq!(cycler.next_due(), Some(start + Duration::from_secs(60)));
+        assert!(cycler.take_due(start).is_empty());
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(cycler.take_due(Instant::now()), vec!["ALL"]);
+
+        let dms_state = WallpaperChange {
+            wallpapers: vec![Wallpaper::new(a.clone())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+        assert!(cycler.advance(&dms_state, &dms_state, "ALL"));
+
+        let mut state = dms_state.clone();
+        cycler.apply_overrides(&mut state);
+        assert_eq!(state.wallpapers, vec![Wallpaper::new(b.clone())]);
+
+        // DMS picking another wallpaper drops the override
+        let mut state = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/other.png".to_string())],
+            ..dms_state
+        };
+        cycler.apply_overrides(&mut state);
+        assert_eq!(state.wallpapers, vec![Wallpaper::new("/other.png".to_string())]);
+
+        let mut disabled = Cycler::disabled();
+        disabled.update(settings_from_session(&session(
+            r#"{"wallpaperCyclingEnabled": true, "wallpaperCyclingInterval": 60}"#,
+        )));
+        assert_eq!(disabled.next_due(), None);
+    }
+}
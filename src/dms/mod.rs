@@ -5,10 +5,22 @@
 
 use crate::error::{DmsAwwwError, Result};
 use crate::config::Config;
+use crate::image::{self, ImageInfo};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Attempts to read a JSON file that is being rewritten
+const READ_ATTEMPTS: u32 = 5;
+
+/// Wait before the second read attempt, doubled after each retry
+const READ_BACKOFF: Duration = Duration::from_millis(10);
 
 /// Parsed DMS session.json structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,13 +33,146 @@ pub struct SessionJson {
     #[serde(rename = "perMonitorWallpaper")]
     pub per_monitor_wallpaper: Option<bool>,
 
-    /// Per-monitor wallpaper mappings
+    /// Per-monitor wallpaper mappings, ordered by output name
     #[serde(rename = "monitorWallpapers", default)]
-    pub monitor_wallpapers: HashMap<String, String>,
+    pub monitor_wallpapers: BTreeMap<String, String>,
 
     /// Whether light mode is enabled
     #[serde(rename = "isLightMode")]
     pub is_light_mode: Option<bool>,
+
+    /// Whether separate light and dark wallpapers are enabled
+    #[serde(rename = "perModeWallpaper")]
+    pub per_mode_wallpaper: Option<bool>,
+
+    /// Wallpaper used in light mode (per-mode wallpaper mode)
+    #[serde(rename = "wallpaperPathLight")]
+    pub wallpaper_path_light: Option<String>,
+
+    /// Wallpaper used in dark mode (per-mode wallpaper mode)
+    #[serde(rename = "wallpaperPathDark")]
+    pub wallpaper_path_dark: Option<String>,
+
+    /// Per-monitor wallpapers used in light mode
+    #[serde(rename = "monitorWallpapersLight", default)]
+    pub monitor_wallpapers_light: BTreeMap<String, String>,
+
+    /// Per-monitor wallpapers used in dark mode
+    #[serde(rename = "monitorWallpapersDark", default)]
+    pub monitor_wallpapers_dark: BTreeMap<String, String>,
+
+    /// Whether wallpaper cycling is enabled (single wallpaper mode)
+    #[serde(rename = "wallpaperCyclingEnabled")]
+    pub wallpaper_cycling_enabled: Option<bool>,
+
+    /// Cycling mode: `interval` or `time`
+    #[serde(rename = "wallpaperCyclingMode")]
+    pub wallpaper_cycling_mode: Option<String>,
+
+    /// Cycling interval in seconds (`interval` mode)
+    #[serde(rename = "wallpaperCyclingInterval")]
+    pub wallpaper_cycling_interval: Option<u64>,
+
+    /// Time of day as `HH:MM` (`time` mode)
+    #[serde(rename = "wallpaperCyclingTime")]
+    pub wallpaper_cycling_time: Option<String>,
+
+    /// Directory to cycle through (defaults to the wallpaper's directory)
+    #[serde(rename = "wallpaperCyclingDirectory")]
+    pub wallpaper_cycling_directory: Option<String>,
+
+    /// Per-monitor cycling settings (per-monitor wallpaper mode)
+    #[serde(rename = "monitorCyclingSettings", default)]
+    pub monitor_cycling_settings: BTreeMap<String, MonitorCyclingJson>,
+}
+
+/// Cycling settings for one monitor in session.json
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+pub struct MonitorCyclingJson {
+    /// Whether cycling is enabled for this monitor
+    pub enabled: Option<bool>,
+
+    /// Cycling mode: `interval` or `time`
+    pub mode: Option<String>,
+
+    /// Cycling interval in seconds
+    pub interval: Option<u64>,
+
+    /// Time of day as `HH:MM`
+    pub time: Option<String>,
+
+    /// Directory to cycle through
+    pub directory: Option<String>,
+}
+
+impl SessionJson {
+    /// Hash the fields dms-awww acts on: wallpapers, mode and cycling settings
+    ///
+    /// Everything else DMS keeps in session.json (UI state, toggles) is left out.
+    pub fn hash_relevant<H: Hasher>(&self, state: &mut H) {
+        self.wallpaper_path.hash(state);
+        self.per_monitor_wallpaper.hash(state);
+        self.monitor_wallpapers.hash(state);
+        self.is_light_mode.hash(state);
+        self.per_mode_wallpaper.hash(state);
+        self.wallpaper_path_light.hash(state);
+        self.wallpaper_path_dark.hash(state);
+        self.monitor_wallpapers_light.hash(state);
+        self.monitor_wallpapers_dark.hash(state);
+        self.wallpaper_cycling_enabled.hash(state);
+        self.wallpaper_cycling_mode.hash(state);
+        self.wallpaper_cycling_interval.hash(state);
+        self.wallpaper_cycling_time.hash(state);
+        self.wallpaper_cycling_directory.hash(state);
+        self.monitor_cycling_settings.hash(state);
+    }
+
+    /// Whether per-mode wallpapers are in effect
+    fn per_mode(&self) -> bool {
+        self.per_mode_wallpaper.unwrap_or(false)
+    }
+
+    /// Main wallpaper for the given mode
+    ///
+    /// Falls back to `wallpaperPath` when per-mode wallpapers are off or the
+    /// mode has no wallpaper set.
+    pub fn wallpaper_for_mode(&self, is_light_mode: bool) -> Option<String> {
+        let per_mode = if is_light_mode {
+            &self.wallpaper_path_light
+        } else {
+            &self.wallpaper_path_dark
+        };
+
+        per_mode
+            .as_ref()
+            .filter(|p| self.per_mode() && !p.is_empty())
+            .or(self.wallpaper_path.as_ref())
+            .cloned()
+    }
+
+    /// Per-monitor wallpapers for the given mode
+    ///
+    /// Monitors without a wallpaper for the mode keep their `monitorWallpapers`
+    /// entry. Ordered by output name, so the resulting wallpapers are too.
+    pub fn monitor_wallpapers_for_mode(&self, is_light_mode: bool) -> BTreeMap<String, String> {
+        let mut wallpapers = self.monitor_wallpapers.clone();
+        if !self.per_mode() {
+            return wallpapers;
+        }
+
+        let per_mode = if is_light_mode {
+            &self.monitor_wallpapers_light
+        } else {
+            &self.monitor_wallpapers_dark
+        };
+        for (monitor, path) in per_mode {
+            if !path.is_empty() {
+                wallpapers.insert(monitor.clone(), path.clone());
+            }
+        }
+
+        wallpapers
+    }
 }
 
 /// Parsed DMS settings.json structure
@@ -45,55 +190,309 @@ pub struct SettingsJson {
 /// Represents a wallpaper change event
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WallpaperChange {
-    /// The wallpaper path(s) to apply
+    /// The wallpaper path(s) to apply; per-monitor ones are ordered by output name
     pub wallpapers: Vec<Wallpaper>,
 
     /// Whether light mode is enabled
     pub is_light_mode: bool,
+
+    /// Matugen scheme type from DMS settings (`matugenScheme`)
+    pub matugen_scheme: String,
+}
+
+/// Difference between two wallpaper states
+///
+/// Tells the executor which stages an apply needs: the wallpaper stage only
+/// for changed monitors, the theme stage whenever anything changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    /// Monitors whose wallpaper changed (`ALL` for a global wallpaper), sorted
+    pub wallpapers_changed: Vec<String>,
+
+    /// Whether light/dark mode flipped
+    pub mode_changed: bool,
+
+    /// Whether the matugen scheme changed
+    pub scheme_changed: bool,
+
+    /// Whether every stage runs whatever the diff says (startup, re-sync)
+    pub resync: bool,
+
+    /// Whether image files were rewritten in place, so outputs already
+    /// showing their paths need them again; forces the apply even while the
+    /// same change is in flight
+    pub rewritten: bool,
+}
+
+impl ChangeSet {
+    /// Everything in `current` needs applying (startup, re-sync)
+    ///
+    /// The mode and scheme only count as changed against `previous`, the state
+    /// last applied, so `on-mode-change` hooks don't run on a mere re-sync.
+    pub fn full(previous: Option<&WallpaperChange>, current: &WallpaperChange) -> Self {
+        Self {
+            wallpapers_changed: current.by_monitor().into_keys().collect(),
+            mode_changed: previous.is_some_and(|p| p.is_light_mode != current.is_light_mode),
+            scheme_changed: previous.is_some_and(|p| p.matugen_scheme != current.matugen_scheme),
+            resync: true,
+            rewritten: false,
+        }
+    }
+
+    /// Re-apply the wallpapers showing `path`, whose file was rewritten in place
+    pub fn rewritten(current: &WallpaperChange, path: &str) -> Self {
+        let wallpapers_changed = current
+            .by_monitor()
+            .into_iter()
+            .filter(|(_, w)| w.path == path)
+            .map(|(monitor, _)| monitor)
+            .collect();
+
+        Self {
+            wallpapers_changed,
+            rewritten: true,
+            ..Self::default()
+        }
+    }
+
+    /// Diff `previous` against `current`
+    pub fn between(previous: &WallpaperChange, current: &WallpaperChange) -> Self {
+        let before = previous.by_monitor();
+        let after = current.by_monitor();
+
+        // Monitors that were added, removed or now show something else
+        let monitors: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        let wallpapers_changed = monitors
+            .into_iter()
+            .filter(|m| before.get(*m) != after.get(*m))
+            .cloned()
+            .collect();
+
+        Self {
+            wallpapers_changed,
+            mode_changed: previous.is_light_mode != current.is_light_mode,
+            scheme_changed: previous.matugen_scheme != current.matugen_scheme,
+            resync: false,
+            rewritten: false,
+        }
+    }
+
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.wallpapers_changed.is_empty() && !self.mode_changed && !self.scheme_changed && !self.resync
+    }
+
+    /// Whether the wallpaper stage has anything to do
+    pub fn needs_wallpaper(&self) -> bool {
+        !self.wallpapers_changed.is_empty()
+    }
+
+    /// Whether the theme stage has anything to do
+    pub fn needs_theme(&self) -> bool {
+        !self.is_empty()
+    }
+
+    /// Whether the wallpaper assigned to `monitor` (`ALL` if global) changed
+    pub fn wallpaper_changed(&self, monitor: &str) -> bool {
+        self.wallpapers_changed.iter().any(|m| m == monitor)
+    }
+
+    /// Add another change set's changes to this one
+    pub fn merge(&mut self, other: &ChangeSet) {
+        let monitors: BTreeSet<String> = self
+            .wallpapers_changed
+            .drain(..)
+            .chain(other.wallpapers_changed.iter().cloned())
+            .collect();
+        self.wallpapers_changed = monitors.into_iter().collect();
+        self.mode_changed |= other.mode_changed;
+        self.scheme_changed |= other.scheme_changed;
+        self.resync |= other.resync;
+        self.rewritten |= other.rewritten;
+    }
+}
+
+impl WallpaperChange {
+    /// Wallpapers keyed by monitor (`ALL` for a global wallpaper)
+    fn by_monitor(&self) -> BTreeMap<String, &Wallpaper> {
+        self.wallpapers
+            .iter()
+            .map(|w| (w.monitor.clone().unwrap_or_else(|| "ALL".to_string()), w))
+            .collect()
+    }
+}
+
+/// What a DMS wallpaper value refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallpaperKind {
+    /// Path to an image file
+    Image,
+    /// Solid color, stored by DMS as `#rrggbb` or `/#rrggbb`
+    Color,
+}
+
+impl WallpaperKind {
+    /// Detect the kind from a raw DMS wallpaper value
+    pub fn detect(value: &str) -> Self {
+        if value.starts_with('#') || value.starts_with("/#") {
+            WallpaperKind::Color
+        } else {
+            WallpaperKind::Image
+        }
+    }
 }
 
 /// Represents a single wallpaper assignment
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Wallpaper {
-    /// Path to the wallpaper image
+    /// Path to the wallpaper image, or the raw color value
     pub path: String,
 
     /// Target monitor (None for all monitors in single mode)
     pub monitor: Option<String>,
+
+    /// Whether this is an image or a solid color
+    pub kind: WallpaperKind,
+
+    /// Image metadata, set once `validate` has checked the file
+    pub image: Option<ImageInfo>,
 }
 
 impl Wallpaper {
     /// Create a new wallpaper for all monitors
     pub fn new(path: String) -> Self {
         Self {
+            kind: WallpaperKind::detect(&path),
             path,
             monitor: None,
+            image: None,
         }
     }
 
     /// Create a new wallpaper for a specific monitor
     pub fn for_monitor(path: String, monitor: String) -> Self {
         Self {
+            kind: WallpaperKind::detect(&path),
             path,
             monitor: Some(monitor),
+            image: None,
         }
     }
 
     /// Check if the wallpaper path is valid (not a color)
     pub fn is_valid_image(&self) -> bool {
-        !self.path.starts_with('#') && !self.path.starts_with("/#")
+        self.kind == WallpaperKind::Image
+    }
+
+    /// Check if this is a well-formed `#rrggbb` color
+    pub fn is_valid_color(&self) -> bool {
+        self.color().is_some()
+    }
+
+    /// Get the color as `#rrggbb`, if this is a well-formed color value
+    pub fn color(&self) -> Option<String> {
+        if self.kind != WallpaperKind::Color {
+            return None;
+        }
+
+        let hex = self.path.trim_start_matches('/').strip_prefix('#')?;
+        if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            Some(format!("#{hex}"))
+        } else {
+            None
+        }
     }
 
     /// Check if the wallpaper file exists
     pub fn exists(&self) -> bool {
         Path::new(&self.path).exists()
     }
+
+    /// Check that an image wallpaper is a supported image and keep its metadata
+    ///
+    /// Colors have nothing to check.
+    pub fn validate(&mut self) -> Result<()> {
+        if self.kind == WallpaperKind::Image && self.image.is_none() {
+            self.image = Some(image::probe(Path::new(&self.path))?);
+        }
+        Ok(())
+    }
+}
+
+/// Read a whole file, reporting whether its size and mtime held still meanwhile
+fn read_snapshot(path: &Path) -> std::io::Result<(String, bool)> {
+    let stat = |path: &Path| -> std::io::Result<(u64, Option<SystemTime>)> {
+        let metadata = fs::metadata(path)?;
+        Ok((metadata.len(), metadata.modified().ok()))
+    };
+
+    let before = stat(path)?;
+    let content = fs::read_to_string(path)?;
+    let after = stat(path)?;
+
+    let stable = before == after && after.0 == content.len() as u64;
+    Ok((content, stable))
+}
+
+/// Read and parse a JSON file that DMS may be rewriting
+///
+/// A file whose size or mtime changes during the read, or that holds
+/// truncated or malformed JSON, is re-read with exponential backoff. Data
+/// errors (valid JSON of the wrong shape) are returned right away.
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let mut backoff = READ_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let (content, stable) = read_snapshot(path)?;
+        let last = attempt == READ_ATTEMPTS;
+
+        if stable || last {
+            match serde_json::from_str(&content) {
+                Ok(value) => return Ok(value),
+                Err(e) if last || e.is_data() => return Err(e.into()),
+                Err(e) => tracing::debug!("{} is not valid JSON yet ({}), retrying", path.display(), e),
+            }
+        } else {
+            tracing::debug!("{} is being written, retrying", path.display());
+        }
+
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// session.json and settings.json as read for one event
+#[derive(Debug, Clone)]
+pub struct DmsSnapshot {
+    /// Parsed session.json (or the last one that parsed)
+    pub session: SessionJson,
+    /// Parsed settings.json (or the last one that parsed), if it exists
+    pub settings: Option<SettingsJson>,
+}
+
+impl DmsSnapshot {
+    /// Hash of the wallpaper-relevant parts of both files
+    ///
+    /// Writes that leave it unchanged can't change what is applied.
+    pub fn fingerprint(&self) -> u64 {
+        let scheme = self.settings.as_ref().and_then(|s| s.matugen_scheme.as_ref());
+
+        let mut hasher = DefaultHasher::new();
+        self.session.hash_relevant(&mut hasher);
+        scheme.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 /// DMS session manager
 pub struct DmsSession {
     config: Config,
-    last_wallpapers: Vec<String>,
+    /// Last session.json that parsed, used while the file is unreadable
+    last_good: Mutex<Option<SessionJson>>,
+    /// Last settings.json that parsed, used while the file is unreadable
+    last_good_settings: Mutex<Option<SettingsJson>>,
 }
 
 impl DmsSession {
@@ -101,11 +500,15 @@ impl DmsSession {
     pub fn new(config: Config) -> Self {
         Self {
             config,
-            last_wallpapers: Vec::new(),
+            last_good: Mutex::new(None),
+            last_good_settings: Mutex::new(None),
         }
     }
 
     /// Read and parse the session.json file
+    ///
+    /// Partial writes are retried; if the file still doesn't parse, the last
+    /// session that did is returned instead.
     pub fn read_session(&self) -> Result<SessionJson> {
         let session_path = self.config.session_file_path();
 
@@ -113,15 +516,31 @@ impl DmsSession {
             return Err(DmsAwwwError::SessionFileNotFound(session_path));
         }
 
-        let content = fs::read_to_string(&session_path)
-            .map_err(|e| DmsAwwwError::Io(e))?;
-
-        let session: SessionJson = serde_json::from_str(&content)?;
-
-        Ok(session)
+        let mut last_good = self.last_good.lock().unwrap_or_else(|e| e.into_inner());
+        match read_json::<SessionJson>(&session_path) {
+            Ok(session) => {
+                *last_good = Some(session.clone());
+                Ok(session)
+            }
+            Err(DmsAwwwError::Json(e)) => match last_good.as_ref() {
+                Some(session) => {
+                    tracing::warn!(
+                        "Failed to parse {} ({}), using last known good state",
+                        session_path.display(),
+                        e
+                    );
+                    Ok(session.clone())
+                }
+                None => Err(DmsAwwwError::Json(e)),
+            },
+            Err(e) => Err(e),
+        }
     }
 
     /// Read and parse the settings.json file
+    ///
+    /// Like `read_session`, a file that can't be read or parsed falls back to
+    /// the last settings that could.
     pub fn read_settings(&self) -> Result<SettingsJson> {
         let settings_path = self.config.settings_file_path();
 
@@ -129,51 +548,96 @@ impl DmsSession {
             return Err(DmsAwwwError::SettingsFileNotFound(settings_path));
         }
 
-        let content = fs::read_to_string(&settings_path)
-            .map_err(|e| DmsAwwwError::Io(e))?;
+        let mut last_good = self.last_good_settings.lock().unwrap_or_else(|e| e.into_inner());
+        match read_json::<SettingsJson>(&settings_path) {
+            Ok(settings) => {
+                *last_good = Some(settings.clone());
+                Ok(settings)
+            }
+            Err(e) => match last_good.as_ref() {
+                Some(settings) => {
+                    tracing::warn!(
+                        "Failed to read {} ({}), using last known good settings",
+                        settings_path.display(),
+                        e
+                    );
+                    Ok(settings.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
 
-        let settings: SettingsJson = serde_json::from_str(&content)?;
+    /// Read session.json and settings.json once, for everything an event needs
+    ///
+    /// Settings are left out only if settings.json doesn't exist, or has never
+    /// been readable.
+    pub fn snapshot(&self) -> Result<DmsSnapshot> {
+        let session = self.read_session()?;
+        let settings = match self.read_settings() {
+            Ok(settings) => Some(settings),
+            Err(DmsAwwwError::SettingsFileNotFound(_)) => None,
+            Err(e) => {
+                tracing::warn!("Ignoring settings.json: {}", e);
+                None
+            }
+        };
+        Ok(DmsSnapshot { session, settings })
+    }
 
-        Ok(settings)
+    /// `snapshot` on the blocking pool, so read retries don't stall the event loop
+    pub async fn load(self: &Arc<Self>) -> Result<DmsSnapshot> {
+        let session = Arc::clone(self);
+        tokio::task::spawn_blocking(move || session.snapshot())
+            .await
+            .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))?
     }
 
     /// Get the current wallpaper state
     pub fn get_current_state(&self) -> Result<WallpaperChange> {
-        let session = self.read_session()?;
-        let settings = self.read_settings().ok();
+        self.state_of(&self.snapshot()?)
+    }
+
+    /// Wallpaper state described by `snapshot`
+    pub fn state_of(&self, snapshot: &DmsSnapshot) -> Result<WallpaperChange> {
+        let session = &snapshot.session;
+        let settings = snapshot.settings.as_ref();
 
         let is_light_mode = session.is_light_mode.unwrap_or(false);
-        let _matugen_scheme = settings
-            .and_then(|s| s.matugen_scheme)
+        let matugen_scheme = settings
+            .and_then(|s| s.matugen_scheme.clone())
             .unwrap_or_else(|| self.config.matugen.default_scheme.clone());
 
         let mut wallpapers = Vec::new();
 
+        // Per-mode wallpapers pick the set matching the current light/dark mode
+        let main_wallpaper = session.wallpaper_for_mode(is_light_mode);
+
         if session.per_monitor_wallpaper.unwrap_or(false) {
             // Per-monitor mode: extract each monitor's wallpaper
-            for (monitor, path) in &session.monitor_wallpapers {
+            for (monitor, path) in session.monitor_wallpapers_for_mode(is_light_mode) {
                 if !path.is_empty() {
-                    wallpapers.push(Wallpaper::for_monitor(path.clone(), monitor.clone()));
+                    wallpapers.push(Wallpaper::for_monitor(path, monitor));
                 }
             }
 
             // If no per-monitor wallpapers found, fall back to main wallpaper
             if wallpapers.is_empty() {
-                if let Some(path) = session.wallpaper_path {
+                if let Some(path) = main_wallpaper {
                     wallpapers.push(Wallpaper::new(path));
                 }
             }
         } else {
             // Single wallpaper mode
-            if let Some(path) = session.wallpaper_path {
+            if let Some(path) = main_wallpaper {
                 wallpapers.push(Wallpaper::new(path));
             }
         }
 
-        // Filter out invalid wallpaper paths (colors, empty strings)
+        // Filter out invalid wallpaper values (malformed colors, empty strings)
         let wallpapers: Vec<Wallpaper> = wallpapers
             .into_iter()
-            .filter(|w| w.is_valid_image() && !w.path.is_empty())
+            .filter(|w| !w.path.is_empty() && (w.is_valid_image() || w.is_valid_color()))
             .collect();
 
         if wallpapers.is_empty() {
@@ -185,25 +649,15 @@ impl DmsSession {
         Ok(WallpaperChange {
             wallpapers,
             is_light_mode,
+            matugen_scheme,
         })
     }
 
-    /// Check if wallpaper has changed since last check
-    pub fn has_changed(&mut self) -> Result<bool> {
-        let state = self.get_current_state()?;
-        let current_paths: Vec<String> = state
-            .wallpapers
-            .iter()
-            .map(|w| w.path.clone())
-            .collect();
-
-        let has_changed = current_paths != self.last_wallpapers;
-
-        if has_changed {
-            self.last_wallpapers = current_paths;
-        }
-
-        Ok(has_changed)
+    /// Hash of the wallpaper-relevant parts of session.json and settings.json
+    ///
+    /// Writes that leave it unchanged can't change what is applied.
+    pub fn fingerprint(&self) -> Result<u64> {
+        Ok(self.snapshot()?.fingerprint())
     }
 
     /// Get the matugen scheme from settings
@@ -288,6 +742,148 @@ mod tests {
         assert_eq!(settings.matugen_scheme, Some("scheme-tonal-spot".to_string()));
     }
 
+    #[test]
+    fn test_get_current_state_from_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let session_path = create_test_session(
+            dir.path(),
+            r#"{"wallpaperPath": "/path/to/wallpaper.jpg", "isLightMode": true}"#,
+        );
+
+        let mut config = Config::default();
+        config.dms.session_file = session_path.to_str().unwrap().to_string();
+        config.dms.settings_file = dir.path().join("settings.json").to_str().unwrap().to_string();
+
+        let session = DmsSession::new(config);
+        let state = session.get_current_state().unwrap();
+        assert_eq!(state.wallpapers, vec![Wallpaper::new("/path/to/wallpaper.jpg".to_string())]);
+        assert!(state.is_light_mode);
+        assert_eq!(state.matugen_scheme, "scheme-tonal-spot");
+
+        // The scheme picked in DMS settings wins over the configured default
+        fs::write(dir.path().join("settings.json"), r#"{"matugenScheme": "scheme-fruit-salad"}"#).unwrap();
+        assert_eq!(session.get_current_state().unwrap().matugen_scheme, "scheme-fruit-salad");
+    }
+
+    #[tokio::test]
+    async fn test_load_snapshot() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let session_path = create_test_session(dir.path(), r#"{"wallpaperPath": "/tmp/a.jpg"}"#);
+        fs::write(dir.path().join("settings.json"), r#"{"matugenScheme": "scheme-fruit-salad"}"#).unwrap();
+
+        let mut config = Config::default();
+        config.dms.session_file = session_path.to_str().unwrap().to_string();
+        config.dms.settings_file = dir.path().join("settings.json").to_str().unwrap().to_string();
+        let session = Arc::new(DmsSession::new(config));
+
+        // One read serves both the fingerprint and the state
+        let snapshot = session.load().await.unwrap();
+        assert_eq!(snapshot.fingerprint(), session.fingerprint().unwrap());
+        let state = session.state_of(&snapshot).unwrap();
+        assert_eq!(state.wallpapers, vec![Wallpaper::new("/tmp/a.jpg".to_string())]);
+        assert_eq!(state.matugen_scheme, "scheme-fruit-salad");
+
+        fs::remove_file(&session_path).unwrap();
+        assert!(matches!(session.load().await, Err(DmsAwwwError::SessionFileNotFound(_))));
+    }
+
+    #[test]
+    fn test_snapshot_keeps_last_good_settings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let session_path = create_test_session(dir.path(), r#"{"wallpaperPath": "/tmp/a.jpg"}"#);
+        let settings_path = dir.path().join("settings.json");
+
+        let mut config = Config::default();
+        config.dms.session_file = session_path.to_str().unwrap().to_string();
+        config.dms.settings_file = settings_path.to_str().unwrap().to_string();
+        let session = DmsSession::new(config);
+
+        fs::write(&settings_path, r#"{"matugenScheme": "scheme-fruit-salad"}"#).unwrap();
+        assert_eq!(session.get_current_state().unwrap().matugen_scheme, "scheme-fruit-salad");
+
+        // A broken write keeps the scheme instead of falling back to the default
+        fs::write(&settings_path, r#"{"matugenScheme": 3}"#).unwrap();
+        let snapshot = session.snapshot().unwrap();
+        assert_eq!(session.state_of(&snapshot).unwrap().matugen_scheme, "scheme-fruit-salad");
+
+        // Only a missing file drops the settings
+        fs::remove_file(&settings_path).unwrap();
+        assert!(session.snapshot().unwrap().settings.is_none());
+    }
+
+    #[test]
+    fn test_read_json_retries_partial_write() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = create_test_session(dir.path(), r#"{"wallpaperPath": "/tmp/a"#);
+
+        // Finish the write while the reader is backing off
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(READ_BACKOFF);
+                fs::write(&path, r#"{"wallpaperPath": "/tmp/a.jpg"}"#).unwrap();
+            })
+        };
+        let session: SessionJson = read_json(&path).unwrap();
+        writer.join().unwrap();
+        assert_eq!(session.wallpaper_path, Some("/tmp/a.jpg".to_string()));
+
+        // Still truncated after every attempt
+        fs::write(&path, "{").unwrap();
+        assert!(matches!(read_json::<SessionJson>(&path), Err(DmsAwwwError::Json(_))));
+
+        // Valid JSON of the wrong shape fails without retrying
+        fs::write(&path, r#"{"wallpaperPath": 1}"#).unwrap();
+        assert!(matches!(read_json::<SessionJson>(&path), Err(DmsAwwwError::Json(e)) if e.is_data()));
+    }
+
+    #[test]
+    fn test_change_set_between() {
+        let previous = WallpaperChange {
+            wallpapers: vec![
+                Wallpaper::for_monitor("/a.png".to_string(), "DP-1".to_string()),
+                Wallpaper::for_monitor("/b.png".to_string(), "HDMI-A-1".to_string()),
+            ],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+
+        assert!(ChangeSet::between(&previous, &previous).is_empty());
+
+        // Mode-only flip needs the theme stage but no wallpaper stage
+        let mut current = previous.clone();
+        current.is_light_mode = true;
+        let changes = ChangeSet::between(&previous, &current);
+        assert!(changes.mode_changed && !changes.scheme_changed);
+        assert!(!changes.needs_wallpaper() && changes.needs_theme());
+
+        // Only the monitor that changed is listed
+        let mut current = previous.clone();
+        current.wallpapers[1] = Wallpaper::for_monitor("/c.png".to_string(), "HDMI-A-1".to_string());
+        let changes = ChangeSet::between(&previous, &current);
+        assert_eq!(changes.wallpapers_changed, vec!["HDMI-A-1"]);
+        assert!(!changes.mode_changed);
+
+        // Switching to a single wallpaper touches every key
+        let current = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/a.png".to_string())],
+            ..previous.clone()
+        };
+        let changes = ChangeSet::between(&previous, &current);
+        assert_eq!(changes.wallpapers_changed, vec!["ALL", "DP-1", "HDMI-A-1"]);
+
+        let mut merged = ChangeSet::between(&previous, &previous);
+        merged.merge(&changes);
+        assert_eq!(merged, changes);
+        assert_eq!(ChangeSet::full(None, &previous).wallpapers_changed, vec!["DP-1", "HDMI-A-1"]);
+
+        // A rewritten image re-applies only the outputs showing it
+        let changes = ChangeSet::rewritten(&previous, "/b.png");
+        assert_eq!(changes.wallpapers_changed, vec!["HDMI-A-1"]);
+        assert!(changes.rewritten && changes.needs_theme());
+        assert!(ChangeSet::rewritten(&previous, "/other.png").is_empty());
+    }
+
     #[test]
     fn test_wallpaper_is_valid_image() {
         let wp = Wallpaper::new("/path/to/image.jpg".to_string());
@@ -299,4 +895,15 @@ mod tests {
         let color_wp2 = Wallpaper::new("/#ff0000".to_string());
         assert!(!color_wp2.is_valid_image());
     }
+
+    #[test]
+    fn test_wallpaper_color() {
+        let wp = Wallpaper::new("/#1E1e2e".to_string());
+        assert_eq!(wp.kind, WallpaperKind::Color);
+        assert_eq!(wp.color(), Some("#1E1e2e".to_string()));
+
+        assert_eq!(Wallpaper::new("#123".to_string()).color(), None);
+        assert_eq!(Wallpaper::new("#gggggg".to_string()).color(), None);
+        assert_eq!(Wallpaper::new("/path/to/image.jpg".to_string()).color(), None);
+    }
 }
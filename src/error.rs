@@ -41,6 +41,10 @@ pub enum DmsAwwwError {
     #[error("Invalid wallpaper path: {0}")]
     InvalidWallpaperPath(String),
 
+    /// Wallpaper file is not a usable image
+    #[error("Invalid wallpaper image {path}: {reason}")]
+    InvalidImage { path: String, reason: String },
+
     /// Niri IPC error
     #[error("Niri IPC error: {0}")]
     NiriIpc(String),
@@ -81,6 +85,14 @@ pub enum DmsAwwwError {
     #[error("Notification error: {0}")]
     NotificationError(#[from] notify::Error),
 
+    /// awww-daemon is not running or not answering
+    #[error("awww-daemon unavailable: {0}")]
+    DaemonUnavailable(String),
+
+    /// The selected wallpaper backend cannot perform an operation
+    #[error("{backend} backend does not support {operation}")]
+    BackendUnsupported { backend: String, operation: String },
+
     /// Timeout waiting for event
     #[error("Timeout waiting for event")]
     Timeout,
@@ -102,6 +114,7 @@ impl DmsAwwwError {
                 | DmsAwwwError::CommandFailed(_, _)
                 | DmsAwwwError::Timeout
                 | DmsAwwwError::Watcher(_)
+                | DmsAwwwError::DaemonUnavailable(_)
         )
     }
 
@@ -111,7 +124,6 @@ impl DmsAwwwError {
             self,
             DmsAwwwError::Config(_)
                 | DmsAwwwError::CommandNotFound(_)
-                | DmsAwwwError::SessionFileNotFound(_)
                 | DmsAwwwError::NoMonitorsDetected
         )
     }
@@ -135,6 +147,12 @@ impl DmsAwwwError {
             DmsAwwwError::InvalidWallpaperPath(path) => {
                 format!("Invalid wallpaper path: {path}\nFile does not exist or is not accessible.")
             }
+            DmsAwwwError::InvalidImage { path, reason } => {
+                format!(
+                    "Wallpaper {path} was rejected: {reason}\nPick a {} image.",
+                    crate::image::ImageFormat::list(" or ")
+                )
+            }
             DmsAwwwError::NiriIpc(msg) => format!("Niri IPC error: {msg}"),
             DmsAwwwError::Watcher(msg) => format!("File watcher error: {msg}"),
             DmsAwwwError::PathExpansion { path, .. } => {
@@ -164,6 +182,12 @@ impl DmsAwwwError {
             DmsAwwwError::NotificationError(err) => {
                 format!("File system notification error: {err}")
             }
+            DmsAwwwError::DaemonUnavailable(msg) => {
+                format!("awww-daemon is not available: {msg}\nStart it with `awww-daemon &` or set awww.daemon.manage = true.")
+            }
+            DmsAwwwError::BackendUnsupported { backend, operation } => {
+                format!("The {backend} backend does not support {operation}.\nChoose another [backend] kind to use it.")
+            }
             DmsAwwwError::Timeout => "Operation timed out".to_string(),
             DmsAwwwError::MultipleErrors(errors) => {
                 format!("Multiple errors occurred:\n{}", errors.join("\n"))
@@ -196,5 +220,12 @@ mod tests {
         let msg = err.user_message();
         assert!(msg.contains("awww"));
         assert!(msg.contains("install"));
+
+        // Lists every format the image checks accept
+        let err = DmsAwwwError::InvalidImage {
+            path: "/tmp/a.xcf".to_string(),
+            reason: "not a supported image".to_string(),
+        };
+        assert!(err.user_message().ends_with("Pick a PNG, JPEG, WebP, GIF, BMP, TIFF, AVIF, JPEG XL, SVG or HEIF image."));
     }
 }
@@ -1,64 +1,201 @@
 //! Command execution for wallpaper and theme updates
 //!
-//! This module handles parallel execution of awww and matugen commands
-//! with proper error handling and logging.
+//! This module applies wallpapers through the configured `WallpaperBackend`
+//! and themes through the configured `ThemeBackend` (sequential or parallel,
+//! per `[executor] order`) with proper error handling and logging.
 
-use crate::config::Config;
-use crate::dms::WallpaperChange;
+use crate::backend::{self, OutputContent, WallpaperBackend};
+use crate::config::{ApplyOrder, Config, HookEvent, RetryConfig, ThemeSourcePolicy};
+use crate::dms::{ChangeSet, Wallpaper, WallpaperChange};
 use crate::error::{DmsAwwwError, Result};
-use tokio::process::Command;
-use tokio::task::JoinSet;
-use which::which;
+use crate::hooks::{self, HookContext};
+use crate::image::{self, ImageCache, ImageFormat, MosaicTile};
+use crate::niri::NiriClient;
+use crate::theme::{self, ThemeBackend, ThemeRequest, ThemeSource};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// What caused a wallpaper apply
+///
+/// Selects which `[awww.events]` transition override is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApplyTrigger {
+    /// Wallpaper restored when the daemon starts
+    Startup,
+    /// Only the light/dark mode flipped
+    ModeChange,
+    /// Regular wallpaper change from DMS
+    #[default]
+    Change,
+}
+
+/// Run `op` until it succeeds, fails with a non-recoverable error, or the
+/// policy's attempts are used up, sleeping with backoff between attempts
+pub async fn with_retry<F, Fut>(policy: &RetryConfig, what: &str, mut op: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(()) => return Ok(()),
+            Err(e) if e.is_recoverable() && attempt < policy.attempts => {
+                let delay = policy.backoff(attempt, jitter_sample());
+                tracing::warn!(
+                    "{} failed (attempt {}/{}): {}; retrying in {}ms",
+                    what, attempt, policy.attempts, e, delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Random value in `[0, 1)` for backoff jitter
+fn jitter_sample() -> f64 {
+    (RandomState::new().hash_one(std::time::SystemTime::now()) >> 11) as f64 / (1u64 << 53) as f64
+}
 
 /// Executor for applying wallpapers and themes
 pub struct Executor {
     config: Config,
     monitors: Vec<String>,
+    backend: Arc<dyn WallpaperBackend>,
+    theme: Arc<dyn ThemeBackend>,
+    image_cache: ImageCache,
 }
 
 impl Executor {
-    /// Create a new executor
+    /// Create a new executor using the backends selected by `[backend] kind`
+    /// and `[theme] kind`
     pub fn new(config: Config, monitors: Vec<String>) -> Self {
-        Self { config, monitors }
+        let backend = backend::create_backend(&config);
+        let theme = theme::create_theme_backend(&config);
+        let image_cache = ImageCache::new(
+            config.image_cache_path(),
+            config.dms.image_cache_max_mb * 1024 * 1024,
+        );
+        Self { config, monitors, backend, theme, image_cache }
+    }
+
+    /// Take over `previous`'s wallpaper backend if the backend settings are unchanged
+    ///
+    /// swaybg's backend owns the processes showing the wallpaper, so a config
+    /// reload keeps it rather than starting over.
+    pub fn keep_backend(&mut self, previous: &Executor) {
+        if self.config.backend == previous.config.backend && self.config.awww == previous.config.awww {
+            self.backend = Arc::clone(&previous.backend);
+        }
     }
 
     /// Check if all required commands are available
     pub fn check_dependencies(&self) -> Result<()> {
         if self.config.awww_enabled() {
-            which("awww").map_err(|_| DmsAwwwError::CommandNotFound("awww".to_string()))?;
-            tracing::debug!("awww found: {}", which("awww").unwrap().display());
+            backend::check_commands(&self.backend.required_commands())?;
         }
 
         if self.config.matugen_enabled() {
-            which("dms").map_err(|_| DmsAwwwError::CommandNotFound("dms".to_string()))?;
-            tracing::debug!("dms found: {}", which("dms").unwrap().display());
+            backend::check_commands(&self.theme.required_commands())?;
         }
 
         Ok(())
     }
 
-    /// Apply wallpaper changes with both awww and matugen in parallel
-    pub async fn apply_wallpaper(&self, change: &WallpaperChange) -> Result<()> {
-        let mut results: Vec<Result<()>> = Vec::new();
+    /// Check that the wallpaper backend answers requests
+    pub async fn check_health(&self) -> Result<()> {
+        if self.config.awww_enabled() {
+            self.backend.health().await?;
+        }
+        Ok(())
+    }
 
-        // Run awww and matugen in parallel
-        let (awww_result, matugen_result) = tokio::join!(
-            self.apply_awww(change),
-            self.apply_matugen(change)
-        );
+    /// Apply wallpaper changes with awww and matugen, ordered per `[executor] order`
+    ///
+    /// Only the stages (and monitors) listed in `changes` run. `[[hooks]]` run
+    /// after each stage and at the end; their failures are logged and never
+    /// change the result. A stage's hooks run alongside the next stage, so a
+    /// slow hook doesn't hold it up.
+    pub async fn apply_wallpaper(
+        &self,
+        change: &WallpaperChange,
+        changes: &ChangeSet,
+        trigger: ApplyTrigger,
+    ) -> Result<()> {
+        let mut results: Vec<Result<()>> = Vec::new();
+        let mut context = HookContext::new(change);
+
+        let awww_timeout = self.config.executor.awww_timeout_ms;
+        let matugen_timeout = self.config.executor.matugen_timeout_ms;
+        let awww = Self::with_timeout("wallpaper", awww_timeout, self.apply_backend(change, changes, trigger));
+        let matugen = Self::with_timeout("matugen", matugen_timeout, self.apply_theme(change, changes));
+
+        // Hooks for a stage that ran and succeeded
+        let hooks = &context;
+        let after_awww = |result: &Result<()>| {
+            let run = result.is_ok() && self.config.awww_enabled() && changes.needs_wallpaper();
+            self.run_hooks_if(run, HookEvent::AfterAwww, hooks)
+        };
+        let after_matugen = |result: &Result<()>| {
+            let run = result.is_ok() && self.config.matugen_enabled() && changes.needs_theme();
+            self.run_hooks_if(run, HookEvent::AfterMatugen, hooks)
+        };
+
+        let (awww_result, matugen_result) = match self.config.executor.order {
+            ApplyOrder::Parallel => tokio::join!(
+                async {
+                    let result = awww.await;
+                    after_awww(&result).await;
+                    result
+                },
+                async {
+                    let result = matugen.await;
+                    after_matugen(&result).await;
+                    result
+                },
+            ),
+            ApplyOrder::AwwwFirst => {
+                let awww_result = awww.await;
+                let (_, matugen_result) = tokio::join!(after_awww(&awww_result), async {
+                    self.stage_delay().await;
+                    let result = matugen.await;
+                    after_matugen(&result).await;
+                    result
+                });
+                (awww_result, matugen_result)
+            }
+            ApplyOrder::MatugenFirst => {
+                let matugen_result = matugen.await;
+                let (_, awww_result) = tokio::join!(after_matugen(&matugen_result), async {
+                    self.stage_delay().await;
+                    let result = awww.await;
+                    after_awww(&result).await;
+                    result
+                });
+                (awww_result, matugen_result)
+            }
+        };
 
         if let Err(e) = awww_result {
-            tracing::error!("awww failed: {}", e);
+            tracing::error!("{} failed: {}", self.backend.name(), e);
             results.push(Err(e));
         }
 
         if let Err(e) = matugen_result {
-            tracing::error!("matugen failed: {}", e);
+            tracing::error!("{} failed: {}", self.theme.name(), e);
             results.push(Err(e));
         }
 
         // Report results
-        if results.is_empty() {
+        let result = if results.is_empty() {
             tracing::info!("Wallpaper applied successfully");
             Ok(())
         } else if results.len() == 1 {
@@ -69,22 +206,106 @@ impl Executor {
                 .filter_map(|e| e.err().map(|err| err.to_string()))
                 .collect();
             Err(DmsAwwwError::MultipleErrors(error_messages))
+        };
+
+        if changes.mode_changed {
+            self.run_hooks(HookEvent::OnModeChange, &context).await;
+        }
+        if let Err(e) = &result {
+            context.error = Some(e.to_string());
+            self.run_hooks(HookEvent::OnError, &context).await;
         }
+
+        result
     }
 
-    /// Apply wallpaper via awww for all monitors
-    async fn apply_awww(&self, change: &WallpaperChange) -> Result<()> {
+    /// Run the hooks registered for `event` if `run` is set
+    async fn run_hooks_if(&self, run: bool, event: HookEvent, context: &HookContext) {
+        if run {
+            self.run_hooks(event, context).await;
+        }
+    }
+
+    /// Run the hooks registered for `event`
+    async fn run_hooks(&self, event: HookEvent, context: &HookContext) {
+        if self.config.hooks.is_empty() {
+            return;
+        }
+
+        let failed = hooks::run_hooks(&self.config.hooks, event, context).await;
+        if failed > 0 {
+            tracing::warn!("{} {} hook(s) failed", failed, event.as_str());
+        }
+    }
+
+    /// Run a stage with a timeout in milliseconds (0 disables it)
+    async fn with_timeout(stage: &str, timeout_ms: u64, fut: impl Future<Output = Result<()>>) -> Result<()> {
+        if timeout_ms == 0 {
+            return fut.await;
+        }
+
+        match tokio::time::timeout(Duration::from_millis(timeout_ms), fut).await {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::error!("{} stage timed out after {}ms", stage, timeout_ms);
+                Err(DmsAwwwError::Timeout)
+            }
+        }
+    }
+
+    /// Wait between sequential stages, if a delay is configured and both stages run
+    async fn stage_delay(&self) {
+        let delay = self.config.executor.stage_delay_ms;
+        if delay > 0 && self.config.awww_enabled() && self.config.matugen_enabled() {
+            tracing::debug!("Waiting {}ms before the next stage", delay);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+    }
+
+    /// Apply wallpaper via the wallpaper backend for all monitors
+    async fn apply_backend(&self, change: &WallpaperChange, changes: &ChangeSet, trigger: ApplyTrigger) -> Result<()> {
         if !self.config.awww_enabled() {
-            tracing::debug!("awww is disabled, skipping");
+            tracing::debug!("Wallpaper backend is disabled, skipping");
+            return Ok(());
+        }
+
+        if !changes.needs_wallpaper() {
+            tracing::debug!("No wallpaper changed, skipping wallpaper stage");
             return Ok(());
         }
 
-        tracing::info!("Applying wallpaper via awww");
+        let name = self.backend.name();
+        tracing::info!("Applying wallpaper via {}", name);
+
+        // Current per-output state, used to skip outputs that already show the image
+        // (a rewritten file shows the same path with new content)
+        let current = if self.config.awww.skip_unchanged && !changes.rewritten {
+            self.query_outputs().await
+        } else {
+            HashMap::new()
+        };
 
         let mut tasks = JoinSet::new();
+        let mut skipped = 0;
+        let mut errors = Vec::new();
 
-        // Create a task for each monitor-wallpaper combination
+        // Create a task for each changed monitor-wallpaper combination
         for wallpaper in &change.wallpapers {
+            let key = wallpaper.monitor.as_deref().unwrap_or("ALL");
+            if !changes.wallpaper_changed(key) {
+                continue;
+            }
+
+            // Reject files the backend can't display before running it
+            let wallpaper = match self.prepare(wallpaper).await {
+                Ok(wallpaper) => wallpaper,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    errors.push(e.to_string());
+                    continue;
+                }
+            };
+
             let monitors_to_apply = if wallpaper.monitor.is_some() {
                 // Per-monitor wallpaper
                 vec![wallpaper.monitor.clone().unwrap()]
@@ -97,17 +318,32 @@ impl Executor {
             };
 
             for monitor in monitors_to_apply {
-                let path = wallpaper.path.clone();
-                let extra_args = self.config.awww.extra_args.clone();
-
+                if Self::is_displaying(&current, &monitor, &wallpaper) {
+                    tracing::debug!("{} already displays {}, skipping", monitor, wallpaper.path);
+                    skipped += 1;
+                    continue;
+                }
+
+                let wallpaper = wallpaper.clone();
+                let backend = Arc::clone(&self.backend);
+                let retry = self.config.executor.retry.clone();
+                let what = format!("{name} on {monitor}");
+
+                // Retries are per monitor so one failing output doesn't re-run the others
                 tasks.spawn(async move {
-                    Self::apply_awww_for_monitor(&path, &monitor, &extra_args).await
+                    with_retry(&retry, &what, || {
+                        Self::apply_for_monitor(backend.as_ref(), &wallpaper, &monitor, trigger)
+                    })
+                    .await
                 });
             }
         }
 
+        if skipped > 0 && tasks.is_empty() {
+            tracing::info!("{} already displays the wallpaper on all outputs", name);
+        }
+
         // Wait for all tasks to complete
-        let mut errors = Vec::new();
         while let Some(result) = tasks.join_next().await {
             if let Err(e) = result {
                 errors.push(format!("Task panic: {}", e));
@@ -123,98 +359,313 @@ impl Executor {
         Ok(())
     }
 
-    /// Apply wallpaper for a single monitor
-    async fn apply_awww_for_monitor(
-        path: &str,
-        monitor: &str,
-        extra_args: &[String],
-    ) -> Result<()> {
-        tracing::debug!("Applying wallpaper {} to monitor {}", path, monitor);
+    /// Validate an image wallpaper and swap in its display copy, if it needs one
+    ///
+    /// The copy (an upright version of a rotated photo, or a PNG of a format
+    /// awww can't read) comes from the image cache, so awww and matugen see
+    /// the same file.
+    async fn prepare(&self, wallpaper: &Wallpaper) -> Result<Wallpaper> {
+        let mut wallpaper = wallpaper.clone();
+        let mut wallpaper = Self::blocking(move || wallpaper.validate().map(|()| wallpaper)).await?;
+        let Some(info) = wallpaper.image else {
+            return Ok(wallpaper);
+        };
+
+        // SVGs are rendered at the resolution of the output they go to
+        let target = if info.format == ImageFormat::Svg {
+            Self::output_size(wallpaper.monitor.as_deref()).await
+        } else {
+            None
+        };
+
+        let cache = self.image_cache.clone();
+        Self::blocking(move || {
+            if let Some(copy) = image::display_copy(Path::new(&wallpaper.path), &info, &cache, target)? {
+                wallpaper.image = Some(image::probe(&copy)?);
+                wallpaper.path = copy.to_string_lossy().into_owned();
+            }
+            Ok(wallpaper)
+        })
+        .await
+    }
 
-        // Verify the file exists
-        if !std::path::Path::new(path).exists() {
-            return Err(DmsAwwwError::InvalidWallpaperPath(path.to_string()));
-        }
+    /// Run file and image work off the async runtime
+    async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+        tokio::task::spawn_blocking(f)
+            .await
+            .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))?
+    }
+
+    /// Resolution of `monitor` from niri; the largest output for `None` (all outputs)
+    async fn output_size(monitor: Option<&str>) -> Option<(u32, u32)> {
+        let outputs = match NiriClient::get_outputs().await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                tracing::debug!("Output sizes unavailable, using the default: {}", e);
+                return None;
+            }
+        };
 
-        let mut cmd = Command::new("awww");
+        outputs
+            .iter()
+            .filter(|o| o.enabled && monitor.is_none_or(|m| o.name == m))
+            .filter_map(|o| o.resolution.as_ref().map(|r| (r.width, r.height)))
+            .max_by_key(|(width, height)| *width as u64 * *height as u64)
+    }
+
+    /// Query what each output currently displays
+    ///
+    /// Failures are logged and yield an empty map, so every output gets applied.
+    async fn query_outputs(&self) -> HashMap<String, OutputContent> {
+        match self.backend.current().await {
+            Ok(current) => current,
+            Err(e) => {
+                tracing::debug!("{} query failed: {}", self.backend.name(), e);
+                HashMap::new()
+            }
+        }
+    }
 
-        // Add extra args if configured
-        for arg in extra_args {
-            cmd.arg(arg);
+    /// Check whether `monitor` already displays `wallpaper`
+    ///
+    /// `ALL` matches only if every queried output displays it.
+    fn is_displaying(
+        current: &HashMap<String, OutputContent>,
+        monitor: &str,
+        wallpaper: &Wallpaper,
+    ) -> bool {
+        if monitor == "ALL" {
+            return !current.is_empty() && current.values().all(|c| c.matches(wallpaper));
         }
 
-        cmd.args(["img", "-o", monitor, path]);
+        current.get(monitor).map(|c| c.matches(wallpaper)).unwrap_or(false)
+    }
 
-        let output = cmd.output().await?;
+    /// Apply wallpaper for a single monitor
+    async fn apply_for_monitor(
+        backend: &dyn WallpaperBackend,
+        wallpaper: &Wallpaper,
+        monitor: &str,
+        trigger: ApplyTrigger,
+    ) -> Result<()> {
+        tracing::debug!("Applying wallpaper {} to monitor {}", wallpaper.path, monitor);
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let code = output.status.code().unwrap_or(-1);
-            tracing::error!("awww failed for {}: {}", monitor, stderr);
-            return Err(DmsAwwwError::CommandFailed("awww".to_string(), code));
+        match wallpaper.color() {
+            Some(color) => backend.clear(monitor, &color).await?,
+            None => backend.apply(monitor, &wallpaper.path, trigger).await?,
         }
 
-        tracing::debug!("awww succeeded for monitor {}", monitor);
+        tracing::debug!("{} succeeded for monitor {}", backend.name(), monitor);
         Ok(())
     }
 
-    /// Apply theme via DMS matugen
-    async fn apply_matugen(&self, change: &WallpaperChange) -> Result<()> {
+    /// Apply theme via the theme generator
+    async fn apply_theme(&self, change: &WallpaperChange, changes: &ChangeSet) -> Result<()> {
         if !self.config.matugen_enabled() {
-            tracing::debug!("matugen is disabled, skipping");
+            tracing::debug!("Theme generation is disabled, skipping");
             return Ok(());
         }
 
-        // Use the first wallpaper for theme generation
-        let wallpaper = change.wallpapers.first()
-            .ok_or_else(|| DmsAwwwError::InvalidWallpaperPath("No wallpapers".to_string()))?;
+        if !changes.needs_theme() {
+            tracing::debug!("Nothing changed, skipping theme stage");
+            return Ok(());
+        }
+
+        let source = self.theme_source(change).await?;
+
+        let request = ThemeRequest {
+            source,
+            is_light_mode: change.is_light_mode,
+            scheme: change.matugen_scheme.clone(),
+        };
 
-        // Verify the file exists
-        if !std::path::Path::new(&wallpaper.path).exists() {
-            return Err(DmsAwwwError::InvalidWallpaperPath(wallpaper.path.clone()));
-        }
-
-        tracing::info!("Triggering DMS matugen for theme update");
-
-        let mode = if change.is_light_mode { "light" } else { "dark" };
-        let matugen_type = self.config.matugen.default_scheme.clone();
-
-        let cache_dir = &self.config.dms.cache_dir;
-        let shell_dir = &self.config.matugen.shell_dir;
-        let config_dir = std::env::var("HOME")
-            .map(|h| format!("{}/.config", h))
-            .unwrap_or_else(|_| "~/.config".to_string());
-
-        let output = Command::new("dms")
-            .args([
-                "matugen", "queue",
-                "--state-dir", cache_dir,
-                "--shell-dir", shell_dir,
-                "--config-dir", &config_dir,
-                "--kind", "image",
-                "--value", &wallpaper.path,
-                "--mode", mode,
-                "--matugen-type", &matugen_type,
-                "--wait",
-            ])
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let code = output.status.code().unwrap_or(-1);
-            tracing::error!("dms matugen failed: {}", stderr);
-            return Err(DmsAwwwError::CommandFailed("dms matugen".to_string(), code));
-        }
-
-        tracing::info!("DMS matugen completed successfully");
+        let name = self.theme.name();
+        tracing::info!("Generating theme via {}", name);
+
+        with_retry(&self.config.executor.retry, name, || self.theme.generate(&request)).await?;
+
+        tracing::info!("{} theme generation completed successfully", name);
         Ok(())
     }
+
+    /// What the theme is generated from, per `[matugen] source`
+    ///
+    /// Solid colors are themed directly; images must be readable images.
+    async fn theme_source(&self, change: &WallpaperChange) -> Result<ThemeSource> {
+        let output = match &self.config.matugen.source {
+            _ if change.wallpapers.len() < 2 => None,
+            ThemeSourcePolicy::Composite => return self.composite_source(change).await,
+            ThemeSourcePolicy::Primary => self.primary_output().await,
+            ThemeSourcePolicy::Focused => NiriClient::focused_output().await.unwrap_or_else(|e| {
+                tracing::debug!("Focused output unavailable: {}", e);
+                None
+            }),
+            ThemeSourcePolicy::Output(name) => Some(name.clone()),
+        };
+
+        let wallpaper = Self::theme_wallpaper(&change.wallpapers, output.as_deref())
+            .ok_or_else(|| DmsAwwwError::InvalidWallpaperPath("No wallpapers".to_string()))?;
+        let wallpaper = self.prepare(wallpaper).await?;
+        Ok(ThemeSource::from_wallpaper(&wallpaper))
+    }
+
+    /// Wallpaper shown on `output`, falling back to the first one (by output name)
+    fn theme_wallpaper<'a>(wallpapers: &'a [Wallpaper], output: Option<&str>) -> Option<&'a Wallpaper> {
+        if let Some(output) = output {
+            match wallpapers.iter().find(|w| w.monitor.as_deref() == Some(output)) {
+                Some(wallpaper) => return Some(wallpaper),
+                None => tracing::debug!("No wallpaper for output {}, theming from the first", output),
+            }
+        }
+        wallpapers.first()
+    }
+
+    /// First `[niri] outputs` entry, else the enabled output at the top left of niri's layout
+    async fn primary_output(&self) -> Option<String> {
+        if let Some(output) = self.config.niri.outputs.first() {
+            return Some(output.clone());
+        }
+
+        let outputs = match NiriClient::get_outputs().await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                tracing::debug!("Output layout unavailable: {}", e);
+                return None;
+            }
+        };
+
+        outputs
+            .into_iter()
+            .filter(|o| o.enabled)
+            .min_by_key(|o| {
+                let (x, y) = o.position.as_ref().map_or((i32::MAX, i32::MAX), |p| (p.x, p.y));
+                (y, x, o.name.clone())
+            })
+            .map(|o| o.name)
+    }
+
+    /// Mosaic of every wallpaper, in output name order
+    async fn composite_source(&self, change: &WallpaperChange) -> Result<ThemeSource> {
+        let mut tiles = Vec::new();
+        for wallpaper in &change.wallpapers {
+            let wallpaper = self.prepare(wallpaper).await?;
+            tiles.push(match wallpaper.color() {
+                Some(color) => MosaicTile::Color(color),
+                None => MosaicTile::Image(PathBuf::from(wallpaper.path)),
+            });
+        }
+
+        let cache = self.image_cache.clone();
+        let mosaic = Self::blocking(move || image::mosaic(&tiles, &cache)).await?;
+        Ok(ThemeSource::Image(mosaic.to_string_lossy().into_owned()))
+    }
+}
+
+/// An apply running in the background
+struct InFlight {
+    change: WallpaperChange,
+    changes: ChangeSet,
+    handle: JoinHandle<Result<()>>,
+}
+
+/// Cancellable apply pipeline
+///
+/// Runs at most one apply at a time. Starting a new apply aborts the one in
+/// flight; its awww and matugen child processes are killed when the aborted
+/// task drops them, so only the newest state wins.
+pub struct ApplyPipeline {
+    executor: Arc<Executor>,
+    in_flight: Option<InFlight>,
+}
+
+impl ApplyPipeline {
+    /// Create a new pipeline around an executor
+    pub fn new(executor: Arc<Executor>) -> Self {
+        Self {
+            executor,
+            in_flight: None,
+        }
+    }
+
+    /// Executor used for applies started from now on
+    pub fn executor(&self) -> &Arc<Executor> {
+        &self.executor
+    }
+
+    /// Use `executor` for applies started from now on
+    ///
+    /// An apply in flight finishes with the executor it started with.
+    pub fn set_executor(&mut self, executor: Arc<Executor>) {
+        self.executor = executor;
+    }
+
+    /// Start applying `change`, superseding any apply in flight
+    ///
+    /// Does nothing if the same change is already being applied, unless
+    /// `changes` is forced by rewritten files: the apply in flight may have
+    /// read them before the rewrite. A superseded apply may have run partway,
+    /// so its change set is redone as well.
+    pub fn start(&mut self, change: WallpaperChange, mut changes: ChangeSet, trigger: ApplyTrigger) {
+        if let Some(current) = &self.in_flight {
+            if current.change == change && !changes.rewritten {
+                tracing::debug!("Identical wallpaper change already in flight");
+                return;
+            }
+            changes.merge(&current.changes);
+        }
+
+        self.cancel();
+
+        let executor = Arc::clone(&self.executor);
+        let task_change = change.clone();
+        let task_changes = changes.clone();
+        let handle = tokio::spawn(async move {
+            executor.apply_wallpaper(&task_change, &task_changes, trigger).await
+        });
+
+        self.in_flight = Some(InFlight { change, changes, handle });
+    }
+
+    /// Abort the apply in flight, if any
+    pub fn cancel(&mut self) {
+        if let Some(current) = self.in_flight.take() {
+            tracing::info!("Superseding in-flight wallpaper apply");
+            current.handle.abort();
+        }
+    }
+
+    /// The change being applied, if any
+    pub fn in_flight(&self) -> Option<&WallpaperChange> {
+        self.in_flight.as_ref().map(|current| &current.change)
+    }
+
+    /// Whether an apply is in flight
+    pub fn is_running(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    /// Wait for the apply in flight to finish
+    ///
+    /// Never resolves while idle. Cancel-safe, so it can be used in `select!`.
+    pub async fn finished(&mut self) -> (WallpaperChange, Result<()>) {
+        let Some(current) = self.in_flight.as_mut() else {
+            return std::future::pending().await;
+        };
+
+        let result = match (&mut current.handle).await {
+            Ok(result) => result,
+            Err(e) => Err(DmsAwwwError::MultipleErrors(vec![format!("Apply task failed: {e}")])),
+        };
+
+        let current = self.in_flight.take().expect("in-flight apply");
+        (current.change, result)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::BackendKind;
 
     #[test]
     fn test_executor_creation() {
@@ -223,4 +674,315 @@ mod tests {
         let executor = Executor::new(config, monitors);
         assert_eq!(executor.monitors.len(), 1);
     }
+
+    #[test]
+    fn test_keep_backend() {
+        let mut config = Config::default();
+        config.backend.kind = BackendKind::Swaybg;
+        let previous = Executor::new(config.clone(), vec![]);
+
+        config.executor.stage_delay_ms = 250;
+        let mut same = Executor::new(config.clone(), vec![]);
+        same.keep_backend(&previous);
+        assert!(Arc::ptr_eq(&same.backend, &previous.backend));
+
+        config.backend.swaybg.mode = "center".to_string();
+        let mut changed = Executor::new(config, vec![]);
+        changed.keep_backend(&previous);
+        assert!(!Arc::ptr_eq(&changed.backend, &previous.backend));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retry_recoverable() {
+        let policy = RetryConfig {
+            attempts: 3,
+            ..RetryConfig::default()
+        };
+
+        let mut calls = 0;
+        let result = with_retry(&policy, "test", || {
+            calls += 1;
+            let fail = calls < 3;
+            async move {
+                if fail {
+                    Err(DmsAwwwError::CommandFailed("awww".to_string(), 1))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retry_gives_up() {
+        let policy = RetryConfig::default();
+
+        // Non-recoverable errors are returned immediately
+        let mut calls = 0;
+        let result = with_retry(&policy, "test", || {
+            calls += 1;
+            async { Err(DmsAwwwError::InvalidWallpaperPath("/nope".to_string())) }
+        })
+        .await;
+        assert!(matches!(result, Err(DmsAwwwError::InvalidWallpaperPath(_))));
+        assert_eq!(calls, 1);
+
+        // Recoverable errors stop after the configured attempts
+        let mut calls = 0;
+        let result = with_retry(&policy, "test", || {
+            calls += 1;
+            async { Err(DmsAwwwError::CommandFailed("awww".to_string(), 1)) }
+        })
+        .await;
+        assert!(matches!(result, Err(DmsAwwwError::CommandFailed(_, 1))));
+        assert_eq!(calls, policy.attempts);
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        let result = Executor::with_timeout("test", 10, slow).await;
+        assert!(matches!(result, Err(DmsAwwwError::Timeout)));
+
+        assert!(Executor::with_timeout("test", 0, async { Ok(()) }).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_newest_change_wins() {
+        let mut config = Config::default();
+        config.awww.enabled = false;
+        config.matugen.enabled = false;
+        let mut pipeline = ApplyPipeline::new(Arc::new(Executor::new(config, vec![])));
+
+        let first = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/tmp/a.png".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+        let second = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/tmp/b.png".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+
+        let changes = ChangeSet::full(None, &first);
+        pipeline.start(first, changes.clone(), ApplyTrigger::Change);
+        pipeline.start(second.clone(), changes, ApplyTrigger::Change);
+        assert!(pipeline.is_running());
+
+        let (change, result) = pipeline.finished().await;
+        assert_eq!(change, second);
+        assert!(result.is_ok());
+        assert!(!pipeline.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_restarts_identical_change_for_rewritten_files() {
+        let mut config = Config::default();
+        config.awww.enabled = false;
+        config.matugen.enabled = false;
+        let mut pipeline = ApplyPipeline::new(Arc::new(Executor::new(config, vec![])));
+
+        let change = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/tmp/a.png".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+
+        pipeline.start(change.clone(), ChangeSet::full(None, &change), ApplyTrigger::Change);
+        let first = pipeline.in_flight.as_ref().unwrap().handle.id();
+
+        // Identical changes are dropped, rewritten files are not
+        pipeline.start(change.clone(), ChangeSet::full(None, &change), ApplyTrigger::Change);
+        assert_eq!(pipeline.in_flight.as_ref().unwrap().handle.id(), first);
+
+        pipeline.start(change.clone(), ChangeSet::rewritten(&change, "/tmp/a.png"), ApplyTrigger::Change);
+        let current = pipeline.in_flight.as_ref().unwrap();
+        assert_ne!(current.handle.id(), first);
+        assert!(current.changes.rewritten);
+        assert!(current.changes.resync, "superseded change set is redone");
+
+        let (finished, result) = pipeline.finished().await;
+        assert_eq!(finished, change);
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hook_failures_do_not_fail_apply() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("mode-changed");
+
+        let mut config = Config::default();
+        config.awww.enabled = false;
+        config.matugen.enabled = false;
+        config.hooks = vec![
+            crate::config::HookConfig {
+                on: HookEvent::OnModeChange,
+                command: "exit 1".to_string(),
+                timeout_ms: 1000,
+            },
+            crate::config::HookConfig {
+                on: HookEvent::OnModeChange,
+                command: format!("echo \"$DMS_AWWW_MODE\" > {}", marker.display()),
+                timeout_ms: 1000,
+            },
+        ];
+        let executor = Executor::new(config, vec![]);
+
+        let change = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/tmp/a.png".to_string())],
+            is_light_mode: true,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+        let changes = ChangeSet {
+            mode_changed: true,
+            ..ChangeSet::default()
+        };
+        assert!(executor.apply_wallpaper(&change, &changes, ApplyTrigger::ModeChange).await.is_ok());
+        assert_eq!(std::fs::read_to_string(&marker).unwrap().trim(), "light");
+    }
+
+    #[tokio::test]
+    async fn test_after_awww_hooks_run_alongside_the_next_stage() {
+        let mut config = Config::default();
+        config.executor.order = ApplyOrder::AwwwFirst;
+        config.executor.stage_delay_ms = 500;
+        config.hooks = vec![crate::config::HookConfig {
+            on: HookEvent::AfterAwww,
+            command: "sleep 0.5".to_string(),
+            timeout_ms: 1000,
+        }];
+        let executor = Executor::new(config, vec!["DP-1".to_string()]);
+
+        // No configured output changes, so the wallpaper stage succeeds without awww
+        let change = WallpaperChange {
+            wallpapers: vec![Wallpaper::for_monitor("/tmp/a.png".to_string(), "HDMI-A-1".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-content".to_string(),
+        };
+        let changes = ChangeSet {
+            wallpapers_changed: vec!["HDMI-A-1".to_string()],
+            ..ChangeSet::default()
+        };
+
+        // The hook overlaps the stage delay instead of adding to it
+        let started = std::time::Instant::now();
+        let _ = executor.apply_wallpaper(&change, &changes, ApplyTrigger::Change).await;
+        assert!(started.elapsed() < Duration::from_millis(900), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_resync_runs_mode_change_hooks_only_on_a_real_flip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("mode-changed");
+
+        let mut config = Config::default();
+        config.awww.enabled = false;
+        config.matugen.enabled = false;
+        config.hooks = vec![crate::config::HookConfig {
+            on: HookEvent::OnModeChange,
+            command: format!("echo \"$DMS_AWWW_MODE\" > {}", marker.display()),
+            timeout_ms: 1000,
+        }];
+        let executor = Executor::new(config, vec![]);
+
+        let dark = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/tmp/a.png".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+        let light = WallpaperChange {
+            is_light_mode: true,
+            ..dark.clone()
+        };
+
+        // Startup, and a reload or daemon restart that finds the same mode
+        for changes in [ChangeSet::full(None, &dark), ChangeSet::full(Some(&dark), &dark)] {
+            assert!(!changes.is_empty());
+            assert!(executor.apply_wallpaper(&dark, &changes, ApplyTrigger::Startup).await.is_ok());
+            assert!(!marker.exists());
+        }
+
+        let changes = ChangeSet::full(Some(&dark), &light);
+        assert!(executor.apply_wallpaper(&light, &changes, ApplyTrigger::Startup).await.is_ok());
+        assert_eq!(std::fs::read_to_string(&marker).unwrap().trim(), "light");
+    }
+
+    #[tokio::test]
+    async fn test_mode_and_scheme_changes_skip_wallpaper_stage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("after-awww");
+
+        let mut config = Config::default();
+        config.matugen.enabled = false;
+        config.hooks = vec![crate::config::HookConfig {
+            on: HookEvent::AfterAwww,
+            command: format!("touch {}", marker.display()),
+            timeout_ms: 1000,
+        }];
+        let executor = Executor::new(config, vec!["DP-1".to_string()]);
+
+        // The image doesn't exist, so running the wallpaper stage would fail
+        let change = WallpaperChange {
+            wallpapers: vec![Wallpaper::new("/nonexistent/a.png".to_string())],
+            is_light_mode: false,
+            matugen_scheme: "scheme-content".to_string(),
+        };
+        let changes = ChangeSet {
+            wallpapers_changed: vec![],
+            mode_changed: true,
+            scheme_changed: true,
+            resync: false,
+            rewritten: false,
+        };
+        assert!(executor.apply_wallpaper(&change, &changes, ApplyTrigger::ModeChange).await.is_ok());
+        assert!(!marker.exists());
+
+        // Monitors not listed in the change set are left alone
+        let changes = ChangeSet {
+            wallpapers_changed: vec!["HDMI-A-1".to_string()],
+            ..ChangeSet::default()
+        };
+        assert!(executor.apply_wallpaper(&change, &changes, ApplyTrigger::Change).await.is_ok());
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_is_displaying() {
+        let current = backend::parse_awww_query(
+            "DP-1: 2560x1440, scale: 1, currently displaying: image: /tmp/a.png\n\
+             HDMI-A-1: 1920x1080, scale: 1, currently displaying: color: 1E1E2E\n",
+        );
+
+        let image = Wallpaper::new("/tmp/a.png".to_string());
+        let color = Wallpaper::new("#1e1e2e".to_string());
+
+        assert!(Executor::is_displaying(&current, "DP-1", &image));
+        assert!(!Executor::is_displaying(&current, "DP-1", &color));
+        assert!(Executor::is_displaying(&current, "HDMI-A-1", &color));
+        assert!(!Executor::is_displaying(&current, "eDP-1", &image));
+        assert!(!Executor::is_displaying(&current, "ALL", &image));
+        assert!(!Executor::is_displaying(&HashMap::new(), "ALL", &image));
+    }
+
+    #[test]
+    fn test_theme_wallpaper() {
+        let wallpapers = vec![
+            Wallpaper::for_monitor("/tmp/a.png".to_string(), "DP-1".to_string()),
+            Wallpaper::for_monitor("/tmp/b.png".to_string(), "HDMI-A-1".to_string()),
+        ];
+
+        let path = |output| Executor::theme_wallpaper(&wallpapers, output).map(|w| w.path.as_str());
+        assert_eq!(path(Some("HDMI-A-1")), Some("/tmp/b.png"));
+        assert_eq!(path(Some("eDP-1")), Some("/tmp/a.png"));
+        assert_eq!(path(None), Some("/tmp/a.png"));
+        assert_eq!(Executor::theme_wallpaper(&[], Some("DP-1")), None);
+    }
 }
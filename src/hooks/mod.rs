@@ -0,0 +1,247 @@
+//! User-defined hooks
+//!
+//! This module runs the `[[hooks]]` commands configured for apply events.
+//! Hooks are run with `sh -c` and receive the wallpaper context through
+//! `DMS_AWWW_*` environment variables. Failures are logged, never returned.
+//! Each hook runs in its own process group, so a timeout or a superseded
+//! apply kills everything the hook started.
+
+use crate::config::{HookConfig, HookEvent};
+use crate::dms::WallpaperChange;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Wallpaper context passed to hooks
+#[derive(Debug, Clone, Default)]
+pub struct HookContext {
+    /// Wallpaper path (or `#rrggbb` color) of the first wallpaper
+    pub wallpaper: String,
+    /// Output of the first wallpaper, `ALL` if it covers every output
+    pub monitor: String,
+    /// Every wallpaper as `(output, path)`, output `ALL` for a global one
+    pub wallpapers: Vec<(String, String)>,
+    /// `light` or `dark`
+    pub mode: String,
+    /// Material scheme type used for theming
+    pub scheme: String,
+    /// Error message, set for `on-error` hooks only
+    pub error: Option<String>,
+}
+
+impl HookContext {
+    /// Build the context for a wallpaper change
+    pub fn new(change: &WallpaperChange) -> Self {
+        let first = change.wallpapers.first();
+        Self {
+            wallpaper: first.map(|w| w.path.clone()).unwrap_or_default(),
+            monitor: first
+                .and_then(|w| w.monitor.clone())
+                .unwrap_or_else(|| "ALL".to_string()),
+            wallpapers: change
+                .wallpapers
+                .iter()
+                .map(|w| (w.monitor.clone().unwrap_or_else(|| "ALL".to_string()), w.path.clone()))
+                .collect(),
+            mode: if change.is_light_mode { "light" } else { "dark" }.to_string(),
+            scheme: change.matugen_scheme.clone(),
+            error: None,
+        }
+    }
+
+    /// Environment variables exported to hook commands
+    ///
+    /// `DMS_AWWW_WALLPAPERS` holds one `output=path` line per wallpaper.
+    fn env(&self, event: HookEvent) -> Vec<(&'static str, String)> {
+        let wallpapers: Vec<String> = self
+            .wallpapers
+            .iter()
+            .map(|(output, path)| format!("{output}={path}"))
+            .collect();
+        let mut env = vec![
+            ("DMS_AWWW_EVENT", event.as_str().to_string()),
+            ("DMS_AWWW_WALLPAPER", self.wallpaper.clone()),
+            ("DMS_AWWW_MONITOR", self.monitor.clone()),
+            ("DMS_AWWW_WALLPAPERS", wallpapers.join("\n")),
+            ("DMS_AWWW_MODE", self.mode.clone()),
+            ("DMS_AWWW_SCHEME", self.scheme.clone()),
+        ];
+        if let Some(error) = &self.error {
+            env.push(("DMS_AWWW_ERROR", error.clone()));
+        }
+        env
+    }
+}
+
+/// Run every hook registered for `event`, in configuration order
+///
+/// Returns the number of hooks that failed or timed out.
+pub async fn run_hooks(hooks: &[HookConfig], event: HookEvent, context: &HookContext) -> usize {
+    let mut failed = 0;
+
+    for hook in hooks.iter().filter(|h| h.on == event) {
+        if let Err(reason) = run_hook(hook, event, context).await {
+            tracing::warn!("{} hook `{}` failed: {}", event.as_str(), hook.command, reason);
+            failed += 1;
+        }
+    }
+
+    failed
+}
+
+/// Kills a hook's process group when dropped, unless the hook finished
+struct ProcessGroup(Option<u32>);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0.and_then(|id| libc::pid_t::try_from(id).ok()) {
+            // SAFETY: kill has no memory-safety preconditions. A group id isn't
+            // reused while any process is left in the group.
+            unsafe {
+                libc::kill(-pgid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Run a single hook to completion or until its timeout
+async fn run_hook(hook: &HookConfig, event: HookEvent, context: &HookContext) -> std::result::Result<(), String> {
+    tracing::debug!("Running {} hook: {}", event.as_str(), hook.command);
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .envs(context.env(event))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut group = ProcessGroup(child.id());
+    let output = child.wait_with_output();
+
+    let output = if hook.timeout_ms == 0 {
+        output.await
+    } else {
+        tokio::time::timeout(Duration::from_millis(hook.timeout_ms), output)
+            .await
+            .map_err(|_| format!("timed out after {}ms", hook.timeout_ms))?
+    };
+
+    // Finished: whatever the hook left running in the background is its own business
+    group.0 = None;
+
+    let output = output.map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "exit code {}: {}",
+            output.status.code().unwrap_or(-1),
+            stderr.trim()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dms::Wallpaper;
+
+    fn hook(on: HookEvent, command: &str) -> HookConfig {
+        HookConfig {
+            on,
+            command: command.to_string(),
+            timeout_ms: 1000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_env() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("env");
+        let change = WallpaperChange {
+            wallpapers: vec![Wallpaper::for_monitor("/tmp/a.png".to_string(), "DP-1".to_string())],
+            is_light_mode: true,
+            matugen_scheme: "scheme-content".to_string(),
+        };
+        let context = HookContext::new(&change);
+
+        let hooks = [
+            hook(
+                HookEvent::AfterAwww,
+                &format!(
+                    "echo \"$DMS_AWWW_EVENT $DMS_AWWW_WALLPAPER $DMS_AWWW_MONITOR $DMS_AWWW_MODE $DMS_AWWW_SCHEME\" > {}",
+                    out.display()
+                ),
+            ),
+            hook(HookEvent::AfterMatugen, "exit 1"),
+        ];
+
+        assert_eq!(run_hooks(&hooks, HookEvent::AfterAwww, &context).await, 0);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap().trim(),
+            "after-awww /tmp/a.png DP-1 light scheme-content"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_env_lists_every_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("env");
+        let change = WallpaperChange {
+            wallpapers: vec![
+                Wallpaper::for_monitor("/tmp/a.png".to_string(), "DP-1".to_string()),
+                Wallpaper::for_monitor("/tmp/my b.png".to_string(), "HDMI-A-1".to_string()),
+            ],
+            is_light_mode: false,
+            matugen_scheme: "scheme-tonal-spot".to_string(),
+        };
+        let context = HookContext::new(&change);
+
+        let hooks = [hook(
+            HookEvent::AfterAwww,
+            &format!(
+                "echo \"$DMS_AWWW_WALLPAPERS\" | while IFS== read -r output path; do echo \"$output:$path\"; done > {}",
+                out.display()
+            ),
+        )];
+
+        assert_eq!(run_hooks(&hooks, HookEvent::AfterAwww, &context).await, 0);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "DP-1:/tmp/a.png\nHDMI-A-1:/tmp/my b.png\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_timeout_kills_process_group() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let marker = dir.path().join("survived");
+        let mut hooks = [hook(
+            HookEvent::OnError,
+            &format!("(sleep 0.5; touch {}) & wait", marker.display()),
+        )];
+        hooks[0].timeout_ms = 100;
+
+        assert_eq!(run_hooks(&hooks, HookEvent::OnError, &HookContext::default()).await, 1);
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert!(!marker.exists(), "background process outlived the hook's timeout");
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_failures_counted() {
+        let context = HookContext::default();
+        let mut hooks = [
+            hook(HookEvent::OnError, "exit 3"),
+            hook(HookEvent::OnError, "sleep 5"),
+            hook(HookEvent::OnError, "true"),
+        ];
+        hooks[1].timeout_ms = 50;
+
+        assert_eq!(run_hooks(&hooks, HookEvent::OnError, &context).await, 2);
+    }
+}
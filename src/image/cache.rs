@@ -0,0 +1,159 @@
+//! Content-addressed cache of display-ready wallpaper copies
+//!
+//! Entries are PNG files named by the SHA-256 of the original's content (plus
+//! a variant suffix, e.g. the raster size). The file mtime records the last
+//! use; once the cache grows past its size cap, the least recently used
+//! entries are removed.
+
+use crate::error::Result;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+/// Sequence number for temporary file names within this process
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Hex SHA-256 of a file's content
+pub fn content_hash(path: &Path) -> Result<String> {
+    let digest = Sha256::digest(fs::read(path)?);
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Directory of cached wallpaper copies with a size cap
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ImageCache {
+    /// Create a cache in `dir` holding at most `max_bytes` (0 for no cap)
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// Cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the entry for `key`
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.png"))
+    }
+
+    /// Existing entry for `key`, marked as just used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = self.entry(key);
+        if !path.is_file() {
+            return None;
+        }
+
+        if let Err(e) = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+        {
+            tracing::debug!("Failed to mark {} as used: {}", path.display(), e);
+        }
+        Some(path)
+    }
+
+    /// Store the entry for `key` written by `write`, then enforce the size cap
+    ///
+    /// `write` gets a temporary path that is renamed into place once complete,
+    /// so concurrent writers and readers never see a partial file.
+    pub fn insert(&self, key: &str, write: impl FnOnce(&Path) -> Result<()>) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.entry(key);
+        let sequence = NEXT_TMP.fetch_add(1, Ordering::Relaxed);
+        // Hidden, and still ending in .png for tools that pick the format by extension
+        let tmp = self.dir.join(format!(".{key}.{}-{sequence}.png", std::process::id()));
+
+        let result = write(&tmp).and_then(|()| Ok(fs::rename(&tmp, &path)?));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result?;
+
+        self.evict(&path);
+        Ok(path)
+    }
+
+    /// Remove least recently used entries until the cache fits its cap, sparing `keep`
+    fn evict(&self, keep: &Path) {
+        if self.max_bytes == 0 {
+            return;
+        }
+
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+            .filter(|p| !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+            .filter_map(|p| {
+                let metadata = fs::metadata(&p).ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), p))
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort();
+
+        for (_, size, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    tracing::debug!("Evicted {} from the image cache", path.display());
+                    total -= size;
+                }
+                Err(e) => tracing::warn!("Failed to evict {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_image_cache_lru_eviction() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ImageCache::new(dir.path().join("cache"), 250);
+        let write = |bytes: usize| move |tmp: &Path| Ok(fs::write(tmp, vec![0u8; bytes])?);
+
+        let a = cache.insert("a", write(100)).unwrap();
+        let b = cache.insert("b", write(100)).unwrap();
+
+        // Using `a` makes `b` the least recently used entry
+        let old = SystemTime::now() - Duration::from_secs(60);
+        for path in [&a, &b] {
+            File::options().write(true).open(path).unwrap().set_modified(old).unwrap();
+        }
+        assert_eq!(cache.get("a"), Some(a.clone()));
+
+        let c = cache.insert("c", write(100)).unwrap();
+        assert!(a.exists());
+        assert!(!b.exists());
+        assert!(c.exists());
+        assert_eq!(cache.get("b"), None);
+
+        // An entry larger than the cap is still kept until the next insert
+        let big = cache.insert("big", write(300)).unwrap();
+        assert!(big.exists());
+        assert!(!a.exists() && !c.exists());
+    }
+}
@@ -0,0 +1,150 @@
+//! Conversion of formats awww can't read
+//!
+//! SVGs are rasterized in process at the output resolution; HEIF/HEIC images
+//! are decoded in process with libheif when built with the `heif` feature.
+//! Both end up as PNGs in the image cache.
+
+use super::cache::{self, ImageCache};
+use super::{ImageFormat, ImageInfo};
+use crate::error::{DmsAwwwError, Result};
+use resvg::{tiny_skia, usvg};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Raster size for SVGs when the output resolution is unknown
+pub const DEFAULT_SVG_SIZE: (u32, u32) = (3840, 2160);
+
+/// PNG copy of an image awww can't read, from the cache or freshly converted
+///
+/// SVGs are rendered to cover `target` (width, height), so each output
+/// resolution gets its own copy.
+pub fn converted_copy(
+    path: &Path,
+    info: &ImageInfo,
+    cache: &ImageCache,
+    target: Option<(u32, u32)>,
+) -> Result<PathBuf> {
+    let hash = cache::content_hash(path)?;
+    let target = target.unwrap_or(DEFAULT_SVG_SIZE);
+    let key = match info.format {
+        ImageFormat::Svg => format!("{hash}-{}x{}", target.0, target.1),
+        _ => hash,
+    };
+
+    if let Some(copy) = cache.get(&key) {
+        tracing::debug!("Using cached conversion {} of {}", copy.display(), path.display());
+        return Ok(copy);
+    }
+
+    tracing::info!("Converting {} image {} to PNG", info.format, path.display());
+    match info.format {
+        ImageFormat::Svg => cache.insert(&key, |tmp| rasterize_svg(path, target, tmp)),
+        ImageFormat::Heif => cache.insert(&key, |tmp| convert_heif(path, tmp)),
+        format => Err(DmsAwwwError::InvalidImage {
+            path: path.display().to_string(),
+            reason: format!("{format} images need no conversion"),
+        }),
+    }
+}
+
+/// Parse an SVG document
+pub(super) fn parse_svg(path: &Path, data: &[u8]) -> Result<usvg::Tree> {
+    usvg::Tree::from_data(data, &usvg::Options::default()).map_err(|e| DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason: format!("invalid SVG: {e}"),
+    })
+}
+
+/// Render an SVG scaled to cover `target`, keeping its aspect ratio
+fn rasterize_svg(path: &Path, target: (u32, u32), out: &Path) -> Result<()> {
+    let tree = parse_svg(path, &fs::read(path)?)?;
+    let size = tree.size();
+    let scale = (target.0 as f32 / size.width()).max(target.1 as f32 / size.height());
+    let width = (size.width() * scale).round() as u32;
+    let height = (size.height() * scale).round() as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or_else(|| DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason: format!("cannot render SVG at {width}x{height}"),
+    })?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    pixmap
+        .save_png(out)
+        .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))
+}
+
+/// Decode a HEIF/HEIC image to PNG with libheif, which also applies its rotation
+#[cfg(feature = "heif")]
+fn convert_heif(path: &Path, out: &Path) -> Result<()> {
+    use ::image::RgbImage;
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let invalid = |reason: String| DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason,
+    };
+    let undecodable = |e: libheif_rs::HeifError| invalid(format!("cannot decode HEIF: {e}"));
+
+    let data = fs::read(path)?;
+    let context = HeifContext::read_from_bytes(&data).map_err(undecodable)?;
+    let handle = context.primary_image_handle().map_err(undecodable)?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(undecodable)?;
+
+    let planes = image.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| invalid("HEIF decoded without RGB data".to_string()))?;
+
+    // Rows may be padded past width * 3 bytes
+    let row_len = plane.width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+
+    let rgb = RgbImage::from_raw(plane.width, plane.height, pixels)
+        .ok_or_else(|| invalid("HEIF decoded to a truncated image".to_string()))?;
+    rgb.save_with_format(out, ::image::ImageFormat::Png)
+        .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))
+}
+
+/// HEIF/HEIC images can't be converted without the `heif` feature
+#[cfg(not(feature = "heif"))]
+fn convert_heif(path: &Path, _out: &Path) -> Result<()> {
+    Err(DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason: "HEIF support is not built in (build dms-awww with the `heif` feature)".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::probe;
+
+    #[test]
+    fn test_converted_copy_svg() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ImageCache::new(dir.path().join("cache"), 0);
+        let svg = dir.path().join("wall.svg");
+        fs::write(
+            &svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="9"><rect width="16" height="9" fill="#336699"/></svg>"##,
+        )
+        .unwrap();
+
+        let info = probe(&svg).unwrap();
+        assert_eq!((info.format, info.width, info.height), (ImageFormat::Svg, 16, 9));
+
+        // Covers the output, keeping the aspect ratio
+        let copy = converted_copy(&svg, &info, &cache, Some((1920, 1200))).unwrap();
+        let png = probe(&copy).unwrap();
+        assert_eq!((png.format, png.width, png.height), (ImageFormat::Png, 2133, 1200));
+
+        // Cached per resolution
+        assert_eq!(converted_copy(&svg, &info, &cache, Some((1920, 1200))).unwrap(), copy);
+        assert_ne!(converted_copy(&svg, &info, &cache, Some((1280, 720))).unwrap(), copy);
+    }
+}
@@ -0,0 +1,585 @@
+//! Image file validation
+//!
+//! This module identifies wallpaper images by their magic bytes and reads
+//! their dimensions from the file header, without decoding any pixel data.
+//! Files awww can't display are rejected with the exact reason.
+//!
+//! Images that need fixing up before display (EXIF orientation, formats awww
+//! can't read) get a copy in a content-addressed cache, see `display_copy`.
+//! The same cache holds mosaics of several wallpapers for theming.
+
+mod cache;
+mod convert;
+mod mosaic;
+mod orientation;
+
+pub use cache::ImageCache;
+pub use convert::{converted_copy, DEFAULT_SVG_SIZE};
+pub use mosaic::{mosaic, MosaicTile};
+pub use orientation::upright_copy;
+
+use crate::error::{DmsAwwwError, Result};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Bytes read up front to identify the format and parse simple headers
+const HEADER_LEN: usize = 64 * 1024;
+
+/// Supported image formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// Portable Network Graphics
+    Png,
+    /// JPEG / JFIF / EXIF
+    Jpeg,
+    /// WebP (lossy, lossless or extended)
+    WebP,
+    /// GIF (first frame is shown)
+    Gif,
+    /// Windows bitmap
+    Bmp,
+    /// TIFF, little or big endian
+    Tiff,
+    /// AV1 image file
+    Avif,
+    /// JPEG XL, bare codestream or container
+    Jxl,
+    /// SVG, rasterized before display
+    Svg,
+    /// HEIF/HEIC, converted before display
+    Heif,
+}
+
+impl ImageFormat {
+    /// Every supported format, in the order they are listed to users
+    pub const ALL: [ImageFormat; 10] = [
+        ImageFormat::Png,
+        ImageFormat::Jpeg,
+        ImageFormat::WebP,
+        ImageFormat::Gif,
+        ImageFormat::Bmp,
+        ImageFormat::Tiff,
+        ImageFormat::Avif,
+        ImageFormat::Jxl,
+        ImageFormat::Svg,
+        ImageFormat::Heif,
+    ];
+
+    /// Names of every supported format, comma-separated with `last` before the final one
+    pub fn list(last: &str) -> String {
+        let names: Vec<&str> = Self::ALL.iter().map(|f| f.as_str()).collect();
+        match names.split_last() {
+            Some((final_name, rest)) => format!("{}{last}{final_name}", rest.join(", ")),
+            None => String::new(),
+        }
+    }
+
+    /// Short uppercase name used in logs and errors
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::WebP => "WebP",
+            ImageFormat::Gif => "GIF",
+            ImageFormat::Bmp => "BMP",
+            ImageFormat::Tiff => "TIFF",
+            ImageFormat::Avif => "AVIF",
+            ImageFormat::Jxl => "JPEG XL",
+            ImageFormat::Svg => "SVG",
+            ImageFormat::Heif => "HEIF",
+        }
+    }
+
+    /// Lowercase file extensions used for this format
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ImageFormat::Png => &["png"],
+            ImageFormat::Jpeg => &["jpg", "jpeg"],
+            ImageFormat::WebP => &["webp"],
+            ImageFormat::Gif => &["gif"],
+            ImageFormat::Bmp => &["bmp"],
+            ImageFormat::Tiff => &["tif", "tiff"],
+            ImageFormat::Avif => &["avif"],
+            ImageFormat::Jxl => &["jxl"],
+            ImageFormat::Svg => &["svg"],
+            ImageFormat::Heif => &["heic", "heif"],
+        }
+    }
+
+    /// Whether awww can't read this format and needs a converted copy
+    pub fn needs_conversion(&self) -> bool {
+        matches!(self, ImageFormat::Svg | ImageFormat::Heif)
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Metadata read from an image header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Detected format
+    pub format: ImageFormat,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+/// Identify an image file and read its dimensions
+///
+/// Fails with `DmsAwwwError::InvalidImage` naming the reason: missing file,
+/// directory, empty file, unsupported or unknown format, or a truncated or
+/// malformed header.
+pub fn probe(path: &Path) -> Result<ImageInfo> {
+    let invalid = |reason: String| DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason,
+    };
+
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => invalid("file does not exist".to_string()),
+        _ => invalid(format!("cannot be read: {e}")),
+    })?;
+    if metadata.is_dir() {
+        return Err(invalid("is a directory".to_string()));
+    }
+    if !metadata.is_file() {
+        return Err(invalid("is not a regular file".to_string()));
+    }
+    if metadata.len() == 0 {
+        return Err(invalid("file is empty".to_string()));
+    }
+
+    let mut file = File::open(path).map_err(|e| invalid(format!("cannot be read: {e}")))?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut file)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .map_err(|e| invalid(format!("cannot be read: {e}")))?;
+
+    let format = sniff(&header).map_err(invalid)?;
+    let (width, height) = dimensions(format, &header, &mut file)
+        .ok_or_else(|| invalid(format!("truncated or malformed {format} header")))?;
+    if width == 0 || height == 0 {
+        return Err(invalid(format!("{format} header has a zero size ({width}x{height})")));
+    }
+
+    Ok(ImageInfo { format, width, height })
+}
+
+/// Display-ready copy of an image for awww and matugen, if the original won't do
+///
+/// Copies live in `cache`, keyed by the original's content hash, so they are
+/// only written once. SVGs are rendered to cover `target` (the output size).
+/// Returns `None` when the original can be used as is.
+pub fn display_copy(
+    path: &Path,
+    info: &ImageInfo,
+    cache: &ImageCache,
+    target: Option<(u32, u32)>,
+) -> Result<Option<PathBuf>> {
+    if info.format.needs_conversion() {
+        return converted_copy(path, info, cache, target).map(Some);
+    }
+    upright_copy(path, info, cache)
+}
+
+/// Identify the format from the leading bytes
+fn sniff(header: &[u8]) -> std::result::Result<ImageFormat, String> {
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Ok(ImageFormat::Png);
+    }
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Ok(ImageFormat::Jpeg);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        return Ok(ImageFormat::WebP);
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Ok(ImageFormat::Gif);
+    }
+    if header.starts_with(b"BM") {
+        return Ok(ImageFormat::Bmp);
+    }
+    if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+        return Ok(ImageFormat::Tiff);
+    }
+    if header.starts_with(&[0xFF, 0x0A]) || header.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n") {
+        return Ok(ImageFormat::Jxl);
+    }
+    if let Some(brands) = ftyp_brands(header) {
+        if brands.iter().any(|b| b == b"avif" || b == b"avis") {
+            return Ok(ImageFormat::Avif);
+        }
+        if brands.iter().any(|b| matches!(b, b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"mif1")) {
+            return Ok(ImageFormat::Heif);
+        }
+    }
+
+    let text = String::from_utf8_lossy(&header[..header.len().min(512)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+        return Ok(ImageFormat::Svg);
+    }
+
+    let magic: Vec<String> = header.iter().take(4).map(|b| format!("{b:02x}")).collect();
+    Err(format!(
+        "not a supported image ({}); starts with {}",
+        ImageFormat::list(", "),
+        magic.join(" ")
+    ))
+}
+
+/// Brands of an ISO base media `ftyp` box, if the file starts with one
+fn ftyp_brands(header: &[u8]) -> Option<Vec<[u8; 4]>> {
+    if header.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let size = (be_u32(header, 0)? as usize).min(header.len());
+    let mut brands = vec![header.get(8..12)?.try_into().ok()?];
+    brands.extend(
+        header
+            .get(16..size)?
+            .chunks_exact(4)
+            .filter_map(|b| <[u8; 4]>::try_from(b).ok()),
+    );
+    Some(brands)
+}
+
+/// Read the dimensions for `format`; `None` if the header is truncated or malformed
+fn dimensions(format: ImageFormat, header: &[u8], file: &mut File) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Png => {
+            if header.get(12..16)? != b"IHDR" {
+                return None;
+            }
+            Some((be_u32(header, 16)?, be_u32(header, 20)?))
+        }
+        ImageFormat::Gif => Some((le_u16(header, 6)? as u32, le_u16(header, 8)? as u32)),
+        ImageFormat::Bmp => {
+            if le_u32(header, 14)? == 12 {
+                Some((le_u16(header, 18)? as u32, le_u16(header, 20)? as u32))
+            } else {
+                let width = le_u32(header, 18)? as i32;
+                let height = le_u32(header, 22)? as i32;
+                Some((width.unsigned_abs(), height.unsigned_abs()))
+            }
+        }
+        ImageFormat::WebP => webp_dimensions(header),
+        ImageFormat::Jpeg => jpeg_dimensions(file),
+        ImageFormat::Tiff => tiff_dimensions(header, file),
+        ImageFormat::Avif | ImageFormat::Heif => {
+            // Image spatial extents: version/flags, then width and height
+            let at = header.windows(4).position(|w| w == b"ispe")?;
+            Some((be_u32(header, at + 8)?, be_u32(header, at + 12)?))
+        }
+        ImageFormat::Jxl => jxl_dimensions(header),
+        ImageFormat::Svg => svg_dimensions(file),
+    }
+}
+
+/// SVG: document size, from `width`/`height` or the `viewBox`
+fn svg_dimensions(file: &mut File) -> Option<(u32, u32)> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_end(&mut data).ok()?;
+    let size = convert::parse_svg(Path::new(""), &data).ok()?.size();
+    Some((size.width().round() as u32, size.height().round() as u32))
+}
+
+/// WebP: lossy (`VP8 `), lossless (`VP8L`) or extended (`VP8X`) header
+fn webp_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    match header.get(12..16)? {
+        b"VP8 " => {
+            if header.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            Some(((le_u16(header, 26)? & 0x3FFF) as u32, (le_u16(header, 28)? & 0x3FFF) as u32))
+        }
+        b"VP8L" => {
+            if *header.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = le_u32(header, 21)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le_u24(header, 24)? + 1, le_u24(header, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// JPEG: walk the marker segments up to the first start-of-frame
+fn jpeg_dimensions(file: &mut File) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        let mut marker = [0u8; 2];
+        read_at(file, pos, &mut marker)?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+        match marker[1] {
+            // Fill byte
+            0xFF => pos += 1,
+            // Standalone markers
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            // End of image or start of scan before any frame header
+            0xD9 | 0xDA => return None,
+            kind => {
+                let mut segment = [0u8; 7];
+                read_at(file, pos + 2, &mut segment)?;
+                if matches!(kind, 0xC0..=0xCF) && !matches!(kind, 0xC4 | 0xC8 | 0xCC) {
+                    let height = u16::from_be_bytes([segment[3], segment[4]]);
+                    let width = u16::from_be_bytes([segment[5], segment[6]]);
+                    return Some((width as u32, height as u32));
+                }
+                pos += 2 + u16::from_be_bytes([segment[0], segment[1]]) as u64;
+            }
+        }
+    }
+}
+
+/// TIFF: `ImageWidth` and `ImageLength` tags of the first IFD
+fn tiff_dimensions(header: &[u8], file: &mut File) -> Option<(u32, u32)> {
+    let little = header.starts_with(b"II");
+    let u16_at = |b: &[u8], at: usize| if little { le_u16(b, at) } else { be_u16(b, at) };
+    let u32_at = |b: &[u8], at: usize| if little { le_u32(b, at) } else { be_u32(b, at) };
+
+    let ifd = u32_at(header, 4)? as u64;
+    let mut count = [0u8; 2];
+    read_at(file, ifd, &mut count)?;
+    let count = u16_at(&count, 0)?.min(1024) as usize;
+    let mut entries = vec![0u8; count * 12];
+    read_at(file, ifd + 2, &mut entries)?;
+
+    let (mut width, mut height) = (None, None);
+    for entry in entries.chunks_exact(12) {
+        let value = match u16_at(entry, 2)? {
+            3 => u16_at(entry, 8)? as u32,
+            4 => u32_at(entry, 8)?,
+            _ => continue,
+        };
+        match u16_at(entry, 0)? {
+            256 => width = Some(value),
+            257 => height = Some(value),
+            _ => {}
+        }
+    }
+    Some((width?, height?))
+}
+
+/// JPEG XL: size header of a bare codestream or of the container's codestream box
+fn jxl_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    let codestream = if header.starts_with(&[0xFF, 0x0A]) {
+        header
+    } else {
+        let mut pos = 0;
+        loop {
+            let size = be_u32(header, pos)? as usize;
+            let (data, end) = match size {
+                0 => (pos + 8, header.len()),
+                1 => (pos + 16, pos + usize::try_from(be_u64(header, pos + 8)?).ok()?),
+                _ => (pos + 8, pos + size),
+            };
+            match header.get(pos + 4..pos + 8)? {
+                b"jxlc" => break header.get(data..)?,
+                // Partial codestream boxes start with a 4-byte index
+                b"jxlp" => break header.get(data + 4..)?,
+                _ => {}
+            }
+            if end <= pos {
+                return None;
+            }
+            pos = end;
+        }
+    };
+    if !codestream.starts_with(&[0xFF, 0x0A]) {
+        return None;
+    }
+
+    let mut bits = BitReader { data: &codestream[2..], pos: 0 };
+    let small = bits.read(1)? == 1;
+    let dimension = |bits: &mut BitReader| -> Option<u32> {
+        if small {
+            return Some((bits.read(5)? + 1) * 8);
+        }
+        let width = [9, 13, 18, 30][bits.read(2)? as usize];
+        Some(bits.read(width)? + 1)
+    };
+
+    let height = dimension(&mut bits)?;
+    let (num, den) = match bits.read(3)? {
+        0 => return Some((dimension(&mut bits)?, height)),
+        1 => (1, 1),
+        2 => (12, 10),
+        3 => (4, 3),
+        4 => (3, 2),
+        5 => (16, 9),
+        6 => (5, 4),
+        _ => (2, 1),
+    };
+    Some(((height as u64 * num / den) as u32, height))
+}
+
+/// Least-significant-bit-first reader for the JPEG XL headers
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            value |= (((byte >> (self.pos % 8)) & 1) as u32) << i;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+/// Fill `buf` from `offset`; `None` past the end of the file
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> Option<()> {
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(buf).ok()
+}
+
+fn be_u16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn le_u16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le_u24(b: &[u8], at: usize) -> Option<u32> {
+    let b = b.get(at..at + 3)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+}
+
+fn le_u32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_bytes(bytes: &[u8]) -> Result<ImageInfo> {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("image");
+        fs::write(&path, bytes).unwrap();
+        probe(&path)
+    }
+
+    fn rejection(bytes: &[u8]) -> String {
+        match probe_bytes(bytes) {
+            Err(DmsAwwwError::InvalidImage { reason, .. }) => reason,
+            other => panic!("expected InvalidImage, got {other:?}"),
+        }
+    }
+
+    fn size(bytes: &[u8]) -> (ImageFormat, u32, u32) {
+        let info = probe_bytes(bytes).unwrap();
+        (info.format, info.width, info.height)
+    }
+
+    #[test]
+    fn test_probe_formats() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(1920u32.to_be_bytes());
+        png.extend(1080u32.to_be_bytes());
+        assert_eq!(size(&png), (ImageFormat::Png, 1920, 1080));
+
+        // SOI, APP0 (JFIF), SOF0
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x04, 0x38, 0x07, 0x80]);
+        assert_eq!(size(&jpeg), (ImageFormat::Jpeg, 1920, 1080));
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([0x80, 0x07, 0x38, 0x04]);
+        assert_eq!(size(&gif), (ImageFormat::Gif, 1920, 1080));
+
+        // Bottom-up BMPs have a negative height
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0u8; 12]);
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(1920i32.to_le_bytes());
+        bmp.extend((-1080i32).to_le_bytes());
+        assert_eq!(size(&bmp), (ImageFormat::Bmp, 1920, 1080));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+        webp.extend(&1919u32.to_le_bytes()[..3]);
+        webp.extend(&1079u32.to_le_bytes()[..3]);
+        assert_eq!(size(&webp), (ImageFormat::WebP, 1920, 1080));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+        webp.extend((1919u32 | (1079 << 14)).to_le_bytes());
+        assert_eq!(size(&webp), (ImageFormat::WebP, 1920, 1080));
+
+        // Big-endian TIFF with one IFD: ImageWidth (LONG), ImageLength (SHORT)
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend(8u32.to_be_bytes());
+        tiff.extend(2u16.to_be_bytes());
+        tiff.extend([0x01, 0x00, 0x00, 0x04, 0, 0, 0, 1]);
+        tiff.extend(1920u32.to_be_bytes());
+        tiff.extend([0x01, 0x01, 0x00, 0x03, 0, 0, 0, 1, 0x04, 0x38, 0, 0]);
+        assert_eq!(size(&tiff), (ImageFormat::Tiff, 1920, 1080));
+
+        let mut avif = b"\0\0\0\x14ftypavif\0\0\0\0mif1".to_vec();
+        avif.extend(b"\0\0\0\x14ispe\0\0\0\0");
+        avif.extend(1920u32.to_be_bytes());
+        avif.extend(1080u32.to_be_bytes());
+        assert_eq!(size(&avif), (ImageFormat::Avif, 1920, 1080));
+
+        let mut heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic".to_vec();
+        heic.extend(b"\0\0\0\x14ispe\0\0\0\0");
+        heic.extend(4032u32.to_be_bytes());
+        heic.extend(3024u32.to_be_bytes());
+        assert_eq!(size(&heic), (ImageFormat::Heif, 4032, 3024));
+
+        // Small size header: height (8+1)*8, 16:9 ratio; then the same in a container
+        let codestream = [0xFF, 0x0A, 0b0101_0001, 0b0000_0001];
+        assert_eq!(size(&codestream), (ImageFormat::Jxl, 128, 72));
+        let mut jxl = b"\0\0\0\x0cJXL \r\n\x87\n".to_vec();
+        jxl.extend(12u32.to_be_bytes());
+        jxl.extend(b"jxlc");
+        jxl.extend(codestream);
+        assert_eq!(size(&jxl), (ImageFormat::Jxl, 128, 72));
+    }
+
+    #[test]
+    fn test_probe_rejections() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let reason = |path: &Path| match probe(path) {
+            Err(DmsAwwwError::InvalidImage { reason, .. }) => reason,
+            other => panic!("expected InvalidImage, got {other:?}"),
+        };
+        assert_eq!(reason(&dir.path().join("missing.png")), "file does not exist");
+        assert_eq!(reason(dir.path()), "is a directory");
+
+        assert_eq!(rejection(b""), "file is empty");
+        assert!(rejection(b"hello world").starts_with("not a supported image"));
+        assert!(rejection(b"hello world").ends_with("68 65 6c 6c"));
+        assert_eq!(rejection(b"\x89PNG\r\n\x1a\n\0\0"), "truncated or malformed PNG header");
+        assert_eq!(rejection(b"<?xml version=\"1.0\"?>\n<svg width=\"0\""), "truncated or malformed SVG header");
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend([0u8; 8]);
+        assert_eq!(rejection(&png), "PNG header has a zero size (0x0)");
+    }
+}
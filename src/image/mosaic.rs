@@ -0,0 +1,116 @@
+//! Mosaic of several wallpapers
+//!
+//! With `[matugen] source = "composite"` the theme is generated from every
+//! output's wallpaper at once. The wallpapers are scaled to equal tiles and
+//! laid out in a row, so each weighs the same in the palette.
+
+use super::cache::{self, ImageCache};
+use crate::error::{DmsAwwwError, Result};
+use ::image::imageops::{self, FilterType};
+use ::image::{Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Size of each wallpaper's tile in the mosaic
+pub const TILE_SIZE: (u32, u32) = (480, 270);
+
+/// One wallpaper in a mosaic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MosaicTile {
+    /// Image file, cropped to cover the tile
+    Image(PathBuf),
+    /// Solid color as `#rrggbb`
+    Color(String),
+}
+
+/// PNG mosaic of `tiles` in order, from the cache or freshly composed
+pub fn mosaic(tiles: &[MosaicTile], cache: &ImageCache) -> Result<PathBuf> {
+    let mut digest = Sha256::new();
+    for tile in tiles {
+        match tile {
+            MosaicTile::Image(path) => digest.update(cache::content_hash(path)?),
+            MosaicTile::Color(color) => digest.update(color),
+        }
+        digest.update([0]);
+    }
+    let hash: String = digest.finalize().iter().map(|b| format!("{b:02x}")).collect();
+    let key = format!("mosaic-{hash}");
+
+    if let Some(copy) = cache.get(&key) {
+        tracing::debug!("Using cached mosaic {}", copy.display());
+        return Ok(copy);
+    }
+
+    let (width, height) = TILE_SIZE;
+    let mut canvas = RgbImage::new(width * tiles.len() as u32, height);
+    for (i, tile) in tiles.iter().enumerate() {
+        let x = i as i64 * width as i64;
+        match tile {
+            MosaicTile::Image(path) => {
+                let image = decode(path)?.resize_to_fill(width, height, FilterType::Triangle);
+                imageops::replace(&mut canvas, &image.to_rgb8(), x, 0);
+            }
+            MosaicTile::Color(color) => {
+                let fill = RgbImage::from_pixel(width, height, parse_color(color)?);
+                imageops::replace(&mut canvas, &fill, x, 0);
+            }
+        }
+    }
+
+    tracing::info!("Writing mosaic of {} wallpapers", tiles.len());
+    cache.insert(&key, |tmp| {
+        canvas
+            .save_with_format(tmp, ::image::ImageFormat::Png)
+            .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))
+    })
+}
+
+/// Decode any image format the `image` crate was built with
+fn decode(path: &Path) -> Result<::image::DynamicImage> {
+    ::image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| DmsAwwwError::InvalidImage {
+            path: path.display().to_string(),
+            reason: format!("cannot be decoded for the mosaic: {e}"),
+        })
+}
+
+/// `#rrggbb` as a pixel
+fn parse_color(color: &str) -> Result<Rgb<u8>> {
+    let invalid = || DmsAwwwError::InvalidWallpaperPath(format!("Invalid color: {color}"));
+    let hex = color.strip_prefix('#').filter(|h| h.len() == 6).ok_or_else(invalid)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mosaic_tiles_in_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ImageCache::new(dir.path().join("cache"), 0);
+
+        let red = dir.path().join("red.png");
+        RgbImage::from_pixel(64, 64, Rgb([255, 0, 0])).save(&red).unwrap();
+        let tiles = vec![
+            MosaicTile::Image(red.clone()),
+            MosaicTile::Color("#0000ff".to_string()),
+        ];
+
+        let path = mosaic(&tiles, &cache).unwrap();
+        let image = ::image::open(&path).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (TILE_SIZE.0 * 2, TILE_SIZE.1));
+        assert_eq!(*image.get_pixel(10, 10), Rgb([255, 0, 0]));
+        assert_eq!(*image.get_pixel(TILE_SIZE.0 + 10, 10), Rgb([0, 0, 255]));
+
+        // Same tiles in the same order reuse the mosaic; another order doesn't
+        assert_eq!(mosaic(&tiles, &cache).unwrap(), path);
+        let reversed: Vec<MosaicTile> = tiles.iter().rev().cloned().collect();
+        assert_ne!(mosaic(&reversed, &cache).unwrap(), path);
+
+        assert!(mosaic(&[MosaicTile::Color("blue".to_string())], &cache).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! EXIF orientation correction
+//!
+//! awww and matugen ignore the EXIF orientation tag, so photos taken with the
+//! camera on its side would show up rotated. Such images get an upright copy
+//! in the image cache.
+
+use super::cache::{self, ImageCache};
+use super::{ImageFormat, ImageInfo};
+use crate::error::{DmsAwwwError, Result};
+use ::image::codecs::jpeg::JpegDecoder;
+use ::image::metadata::Orientation;
+use ::image::{DynamicImage, ImageDecoder};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Write an upright copy of a JPEG into `cache` if its EXIF orientation needs one
+///
+/// Returns the copy, reused when the same content was seen before, or `None`
+/// if the image is already upright. Only the headers are read for upright images.
+pub fn upright_copy(path: &Path, info: &ImageInfo, cache: &ImageCache) -> Result<Option<PathBuf>> {
+    if info.format != ImageFormat::Jpeg {
+        return Ok(None);
+    }
+
+    let decode_error = |e: ::image::ImageError| DmsAwwwError::InvalidImage {
+        path: path.display().to_string(),
+        reason: format!("cannot be decoded: {e}"),
+    };
+
+    let mut decoder = JpegDecoder::new(BufReader::new(File::open(path)?)).map_err(decode_error)?;
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    if orientation == Orientation::NoTransforms {
+        return Ok(None);
+    }
+
+    let key = cache::content_hash(path)?;
+    if let Some(copy) = cache.get(&key) {
+        tracing::debug!("Using cached upright copy {} of {}", copy.display(), path.display());
+        return Ok(Some(copy));
+    }
+
+    tracing::info!("Writing upright copy of {} ({:?})", path.display(), orientation);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    let copy = cache.insert(&key, |tmp| {
+        image
+            .save_with_format(tmp, ::image::ImageFormat::Png)
+            .map_err(|e| DmsAwwwError::Io(std::io::Error::other(e)))
+    })?;
+
+    Ok(Some(copy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::probe;
+    use ::image::codecs::jpeg::JpegEncoder;
+    use ::image::{ExtendedColorType, RgbImage};
+    use std::fs;
+
+    /// 2x1 JPEG, with an EXIF orientation tag if given
+    fn jpeg(orientation: Option<u16>) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        let pixels = RgbImage::from_pixel(2, 1, ::image::Rgb([200, 40, 40]));
+        JpegEncoder::new(&mut jpeg)
+            .encode(&pixels, 2, 1, ExtendedColorType::Rgb8)
+            .unwrap();
+
+        if let Some(orientation) = orientation {
+            // APP1 Exif: little-endian TIFF header, one IFD entry (Orientation, SHORT)
+            let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec();
+            exif.extend(orientation.to_le_bytes());
+            exif.extend([0u8; 6]);
+            let mut app1 = vec![0xFF, 0xE1];
+            app1.extend(((exif.len() + 2) as u16).to_be_bytes());
+            app1.extend(exif);
+            jpeg.splice(2..2, app1);
+        }
+        jpeg
+    }
+
+    #[test]
+    fn test_upright_copy() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let cache = ImageCache::new(cache_dir.clone(), 0);
+
+        let upright = dir.path().join("upright.jpg");
+        fs::write(&upright, jpeg(None)).unwrap();
+        let info = probe(&upright).unwrap();
+        assert_eq!(upright_copy(&upright, &info, &cache).unwrap(), None);
+
+        // Orientation 6: stored sideways, shown rotated 90° clockwise
+        let rotated = dir.path().join("rotated.jpg");
+        fs::write(&rotated, jpeg(Some(6))).unwrap();
+        let info = probe(&rotated).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+
+        let copy = upright_copy(&rotated, &info, &cache).unwrap().unwrap();
+        assert!(copy.starts_with(&cache_dir));
+        let copy_info = probe(&copy).unwrap();
+        assert_eq!(copy_info.format, ImageFormat::Png);
+        assert_eq!((copy_info.width, copy_info.height), (1, 2));
+
+        // Same content, same cached copy
+        let again = dir.path().join("again.jpg");
+        fs::copy(&rotated, &again).unwrap();
+        assert_eq!(upright_copy(&again, &info, &cache).unwrap(), Some(copy));
+    }
+}
@@ -3,9 +3,15 @@
 //! This library provides the core functionality for the dms-awww daemon,
 //! which manages wallpaper changes for DMS using awww.
 
+pub mod backend;
 pub mod config;
+pub mod cycling;
 pub mod dms;
 pub mod error;
 pub mod executor;
+pub mod hooks;
+pub mod image;
 pub mod niri;
+pub mod supervisor;
+pub mod theme;
 pub mod watcher;
@@ -3,21 +3,24 @@
 //! This daemon monitors DMS session.json for wallpaper changes and applies
 //! them via awww (efficient, no VRAM) while keeping themes in sync via matugen.
 
-mod config;
-mod dms;
-mod error;
-mod executor;
-mod niri;
-mod watcher;
-
-use crate::config::Config;
-use crate::dms::DmsSession;
-use crate::error::{DmsAwwwError, Result};
-use crate::executor::Executor;
-use crate::watcher::DebouncedWatcher;
 use clap::Parser;
+use dms_awww::config::{AwwwDaemonConfig, BackendKind, Config};
+use dms_awww::cycling::{self, Cycler};
+use dms_awww::dms::{ChangeSet, DmsSession, DmsSnapshot, WallpaperChange, WallpaperKind};
+use dms_awww::error::{DmsAwwwError, Result};
+use dms_awww::executor::{ApplyPipeline, ApplyTrigger, Executor};
+use dms_awww::niri;
+use dms_awww::supervisor::{AwwwSupervisor, DaemonStatus};
+use dms_awww::watcher::{ChangeFilter, Debounce, WatchOptions, WatchSet};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info, warn, Level};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn, Level};
 use tracing_subscriber::{
     fmt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
 };
@@ -28,7 +31,7 @@ use tracing_subscriber::{
 struct Args {
     /// Configuration file path
     #[arg(short, long)]
-    config: Option<String>,
+    config: Option<PathBuf>,
 
     /// Log level (overrides config)
     #[arg(short, long)]
@@ -44,7 +47,7 @@ struct Args {
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = Args::parse();
 
     // Initialize logging
@@ -61,12 +64,12 @@ async fn main() {
     info!("dms-awww v{} starting", env!("CARGO_PKG_VERSION"));
 
     // Load configuration
-    let config = match Config::load() {
+    let config = match Config::load_from(args.config.as_deref()) {
         Ok(c) => c,
         Err(e) => {
             error!("Failed to load configuration: {}", e);
             eprintln!("Error: {}", e.user_message());
-            std::process::exit(1);
+            return ExitCode::FAILURE;
         }
     };
 
@@ -88,19 +91,21 @@ async fn main() {
     if let Err(e) = config.validate() {
         error!("Configuration validation failed: {}", e);
         eprintln!("Configuration error: {}", e);
-        std::process::exit(1);
+        return ExitCode::FAILURE;
     }
 
     // Run the main application
-    let result = run(config, args.once).await;
+    let result = run(config, args.config, args.once).await;
 
+    // Return rather than exit, so dropping the runtime stops a managed awww-daemon
     if let Err(e) = result {
         error!("Fatal error: {}", e);
         eprintln!("Error: {}", e.user_message());
-        std::process::exit(1);
+        return ExitCode::FAILURE;
     }
 
     info!("dms-awww shutting down");
+    ExitCode::SUCCESS
 }
 
 /// Initialize logging with console output
@@ -131,99 +136,581 @@ fn parse_log_level(s: &str) -> Level {
 }
 
 /// Main application logic
-async fn run(config: Config, once: bool) -> Result<()> {
+///
+/// `config_path` is the `--config` file, if given, and is reloaded on changes.
+async fn run(mut config: Config, config_path: Option<PathBuf>, once: bool) -> Result<()> {
     // Create DMS session manager
-    let session = DmsSession::new(config.clone());
-
-    // Get monitor outputs
-    let monitors = if config.general.auto_detect_monitors {
-        info!("Auto-detecting monitors...");
-        niri::get_monitor_outputs(config.get_monitor_outputs(), true).await?
-    } else {
-        config.get_monitor_outputs()
-    };
-
-    if monitors.is_empty() {
-        warn!("No monitors configured, using 'ALL' as fallback");
-    } else {
-        info!("Using monitors: {:?}", monitors);
-    }
+    let mut session = Arc::new(DmsSession::new(config.clone()));
 
     // Create executor
-    let executor = Executor::new(config.clone(), monitors);
+    let executor = Executor::new(config.clone(), detect_monitors(&config).await?);
 
     // Check dependencies
     executor.check_dependencies()?;
+    check_backend_health(&executor, &config).await;
+
+    // Applies run in the background; a newer change supersedes the one in flight
+    let mut pipeline = ApplyPipeline::new(Arc::new(executor));
+
+    // Supervise awww-daemon so applies can wait for it
+    let mut daemon_rx = spawn_supervisor(&config);
+
+    // Last successfully applied state, used to classify the next change
+    let mut last_state: Option<WallpaperChange> = None;
+
+    // Whether an apply is queued until awww-daemon is ready
+    let mut pending = false;
+
+    // DMS wallpaper cycling, scheduled from session.json
+    let mut cycler = if config.cycling.enabled {
+        Cycler::new()
+    } else {
+        Cycler::disabled()
+    };
 
     // Initial wallpaper check/apply
     info!("Performing initial wallpaper check...");
-    if let Err(e) = apply_wallpaper_once(&session, &executor).await {
-        warn!("Initial wallpaper check failed: {}", e);
-        // Don't fail on initial check - file might not exist yet
+    if let Some(rx) = daemon_rx.as_mut() {
+        let ready_timeout = Duration::from_millis(config.awww.daemon.ready_timeout_ms);
+        if let Err(e) = AwwwSupervisor::wait_ready(rx, ready_timeout).await {
+            // With --once there is no later readiness to apply on
+            if once {
+                return Err(e);
+            }
+            warn!("{}; queueing initial wallpaper apply", e);
+            pending = true;
+        }
+    }
+    // Whether session.json is there, and which writes change the wallpaper state
+    let snapshot = session.load().await;
+    let mut files = DmsFiles::new(&config, &snapshot);
+
+    if !pending {
+        match start_apply(&session, snapshot, &mut pipeline, &last_state, &mut cycler, true) {
+            Err(DmsAwwwError::SessionFileNotFound(path)) => {
+                warn!("{} does not exist yet, waiting for DMS to create it", path.display());
+            }
+            // Don't fail on initial check - the file may be mid-write
+            Err(e) => warn!("Initial wallpaper check failed: {}", e),
+            Ok(()) => {}
+        }
     }
 
     if once {
+        if pipeline.is_running() {
+            if let (_, Err(e)) = pipeline.finished().await {
+                warn!("Initial wallpaper apply failed: {}", e);
+            }
+        }
         info!("Running once, exiting...");
         return Ok(());
     }
 
-    // Start file watcher
-    info!("Starting file watcher for: {}", config.dms.session_file);
-    let mut watcher = DebouncedWatcher::new(
-        config.session_file_path(),
-        config.general.debounce_ms,
-    )?;
+    // Watch DMS's files, our config file and the wallpaper images on one stream
+    let mut watches = WatchSet::new(WatchOptions::from_config(&config.general));
+    let config_file = Config::config_file_path(config_path.as_deref());
+    watch_files(&mut watches, &config, &config_file)?;
+
+    // SIGHUP reloads the config file, as a change to it does
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    // SIGTERM (systemctl stop) and SIGINT end the loop, so children are cleaned up
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
 
     info!("Entering event loop (Ctrl+C to exit)");
 
     // Main event loop
     loop {
-        match watcher.next().await {
-            Ok(Some(event)) => {
-                info!("File event: {:?}", event);
+        let mut reload = false;
 
-                // Small delay to let the file write complete
-                tokio::time::sleep(Duration::from_millis(50)).await;
+        tokio::select! {
+            event = watches.next() => match event {
+                Some((Input::Session, event)) => {
+                    info!("File event: {:?}", event);
+                    sync_dms(&session, &mut files, &daemon_rx, &mut pending, &mut pipeline, &last_state, &mut cycler).await?;
+                }
+                Some((Input::Settings, event)) => {
+                    info!("Settings event: {:?}", event);
+                    sync_dms(&session, &mut files, &daemon_rx, &mut pending, &mut pipeline, &last_state, &mut cycler).await?;
+                }
+                Some((Input::Config, event)) => {
+                    info!("Config file {} changed ({:?}), reloading", config_file.display(), event);
+                    reload = true;
+                }
+                Some((Input::Image(path), event)) => {
+                    // Only files rewritten under the same name; a removed file is kept on screen.
+                    // Build on the change in flight, so the re-apply doesn't roll it back
+                    let state = pipeline.in_flight().or(last_state.as_ref()).cloned();
+                    let Some(state) = state.filter(|_| path.is_file()) else {
+                        continue;
+                    };
+                    let changes = ChangeSet::rewritten(&state, &path.to_string_lossy());
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    info!("Wallpaper {} changed on disk ({:?}), re-applying", path.display(), event);
+
+                    if !daemon_ready(&daemon_rx) {
+                        pending = true;
+                        continue;
+                    }
+                    pipeline.start(state, changes, ApplyTrigger::Change);
+                }
+                None => {
+                    warn!("File watcher channel closed");
+                    return Err(DmsAwwwError::Watcher("Channel closed".to_string()));
+                }
+            },
+            _ = wait_until(files.recheck) => {
+                files.recheck = None;
+                sync_dms(&session, &mut files, &daemon_rx, &mut pending, &mut pipeline, &last_state, &mut cycler).await?;
+            },
+            _ = cycler.wait(), if !files.missing => {
+                let due = cycler.take_due(Instant::now());
+
+                if !daemon_ready(&daemon_rx) {
+                    debug!("Skipping wallpaper cycle, awww-daemon not ready");
+                    continue;
+                }
 
-                if let Err(e) = apply_wallpaper_once(&session, &executor).await {
+                let snapshot = match session.load().await {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        warn!("Wallpaper cycling failed: {}", e);
+                        continue;
+                    }
+                };
+                match advance_cycling(&session, &snapshot, &mut cycler, &due) {
+                    Ok(true) => {
+                        if let Err(e) = start_apply(&session, Ok(snapshot), &mut pipeline, &last_state, &mut cycler, false) {
+                            error!("Failed to apply cycled wallpaper: {}", e);
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("Wallpaper cycling failed: {}", e),
+                }
+            },
+            (change, result) = pipeline.finished() => match result {
+                Ok(()) => {
+                    watch_images(&mut watches, &change, &config);
+                    last_state = Some(change);
+                }
+                Err(e) => {
                     error!("Failed to apply wallpaper: {}", e);
 
                     if e.is_critical() {
                         return Err(e);
                     }
                 }
+            },
+            status = next_daemon_status(&mut daemon_rx) => match status {
+                Some(DaemonStatus::Ready) => {
+                    // A (re)started daemon has no wallpaper; restore the current state
+                    if pending || last_state.is_some() {
+                        info!("awww-daemon is ready, re-applying current wallpaper");
+                        pending = false;
+
+                        if let Err(e) = start_apply(&session, session.load().await, &mut pipeline, &last_state, &mut cycler, true) {
+                            error!("Failed to re-apply wallpaper: {}", e);
+                        }
+                    }
+                }
+                Some(DaemonStatus::Down) => {
+                    warn!("awww-daemon is down, wallpaper applies will be queued");
+                }
+                Some(DaemonStatus::Unknown) => {}
+                None => {
+                    warn!("awww-daemon supervisor stopped");
+                    daemon_rx = None;
+                }
+            },
+            _ = hangup.recv() => {
+                info!("Received SIGHUP, reloading configuration");
+                reload = true;
+            },
+            _ = terminate.recv() => {
+                info!("Received SIGTERM, exiting");
+                return Ok(());
+            },
+            _ = interrupt.recv() => {
+                info!("Received SIGINT, exiting");
+                return Ok(());
+            },
+        }
+
+        if !reload {
+            continue;
+        }
+
+        let (new_config, mut executor) = match load_config(config_path.as_deref()).await {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                error!("Failed to reload configuration, keeping the current one: {}", e.user_message());
+                continue;
             }
-            Ok(None) => {
-                warn!("File watcher channel closed");
-                return Err(DmsAwwwError::Watcher("Channel closed".to_string()));
+        };
+        if new_config == config {
+            info!("Configuration unchanged");
+            continue;
+        }
+
+        // The session and the applies started from now on use the new config
+        session = Arc::new(DmsSession::new(new_config.clone()));
+        executor.keep_backend(pipeline.executor());
+        pipeline.set_executor(Arc::new(executor));
+
+        if new_config.cycling.enabled != config.cycling.enabled {
+            cycler = if new_config.cycling.enabled {
+                Cycler::new()
+            } else {
+                Cycler::disabled()
+            };
+        }
+
+        if supervision(&new_config) != supervision(&config) {
+            // The old supervisor stops once its receiver is dropped
+            daemon_rx = spawn_supervisor(&new_config);
+        }
+
+        if new_config.dms != config.dms || new_config.general != config.general {
+            if let Err(e) = watch_files(&mut watches, &new_config, &config_file) {
+                error!("Failed to watch the session file: {}", e);
             }
-            Err(e) => {
-                error!("Watcher error: {}", e);
+            files = DmsFiles::new(&new_config, &session.load().await);
+        }
 
-                let error: DmsAwwwError = e;
-                if error.is_critical() {
-                    return Err(error);
-                }
+        if new_config.general.log_level != config.general.log_level {
+            warn!("log_level changes take effect when dms-awww restarts");
+        }
+
+        let reapply = config.needs_reapply(&new_config);
+        config = new_config;
+        info!("Configuration reloaded");
+
+        if !reapply || files.missing {
+            continue;
+        }
+        if !daemon_ready(&daemon_rx) {
+            info!("awww-daemon not ready, queueing wallpaper apply");
+            pending = true;
+        } else if let Err(e) = start_apply(&session, session.load().await, &mut pipeline, &last_state, &mut cycler, true) {
+            error!("Failed to re-apply wallpaper: {}", e);
+        }
+    }
+}
+
+/// Load and validate the config file, with an executor ready to use it
+async fn load_config(path: Option<&Path>) -> Result<(Config, Executor)> {
+    let config = Config::load_from(path)?;
+    config.validate()?;
+
+    let executor = Executor::new(config.clone(), detect_monitors(&config).await?);
+    executor.check_dependencies()?;
+    check_backend_health(&executor, &config).await;
+
+    Ok((config, executor))
+}
+
+/// Warn if the wallpaper backend doesn't answer yet
+///
+/// awww-daemon is left to its supervisor, which queues applies until it is ready.
+async fn check_backend_health(executor: &Executor, config: &Config) {
+    if supervision(config).is_some() {
+        return;
+    }
+    if let Err(e) = executor.check_health().await {
+        warn!("Wallpaper backend is not ready, applies may fail until it is: {}", e);
+    }
+}
+
+/// Monitor outputs to apply to, configured or detected via niri
+async fn detect_monitors(config: &Config) -> Result<Vec<String>> {
+    let monitors = if config.general.auto_detect_monitors {
+        info!("Auto-detecting monitors...");
+        niri::get_monitor_outputs(config.get_monitor_outputs(), true).await?
+    } else {
+        config.get_monitor_outputs()
+    };
+
+    if monitors.is_empty() {
+        warn!("No monitors configured, using 'ALL' as fallback");
+    } else {
+        info!("Using monitors: {:?}", monitors);
+    }
+    Ok(monitors)
+}
+
+/// Settings the awww-daemon supervisor runs with, or None when it isn't needed
+fn supervision(config: &Config) -> Option<(&AwwwDaemonConfig, &[String])> {
+    (config.awww_enabled() && config.backend.kind == BackendKind::Awww)
+        .then_some((&config.awww.daemon, config.awww.extra_args.as_slice()))
+}
+
+/// Supervise awww-daemon if the config uses it, returning its status receiver
+fn spawn_supervisor(config: &Config) -> Option<watch::Receiver<DaemonStatus>> {
+    supervision(config).map(|_| AwwwSupervisor::new(config).spawn())
+}
+
+/// Whether awww-daemon can accept applies (always true without a supervisor)
+fn daemon_ready(rx: &Option<watch::Receiver<DaemonStatus>>) -> bool {
+    rx.as_ref()
+        .map(|rx| *rx.borrow() == DaemonStatus::Ready)
+        .unwrap_or(true)
+}
+
+/// Wait for the next awww-daemon status change
+///
+/// Never resolves when there is no supervisor; returns None if it stopped.
+async fn next_daemon_status(rx: &mut Option<watch::Receiver<DaemonStatus>>) -> Option<DaemonStatus> {
+    match rx {
+        Some(rx) => match rx.changed().await {
+            Ok(()) => Some(*rx.borrow_and_update()),
+            Err(_) => None,
+        },
+        None => std::future::pending().await,
+    }
+}
+
+/// Files the main loop watches
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Input {
+    /// DMS session.json
+    Session,
+    /// DMS settings.json
+    Settings,
+    /// dms-awww's own config file
+    Config,
+    /// A wallpaper image currently on screen
+    Image(PathBuf),
+}
+
+/// Debounce for watched files, per `[general] debounce_ms`
+fn debounce(config: &Config) -> Debounce {
+    Debounce::new(
+        Duration::from_millis(config.general.debounce_ms),
+        Duration::from_millis(config.general.debounce_max_wait_ms),
+    )
+}
+
+/// Watch session.json, settings.json and the config file, replacing earlier watches of them
+///
+/// Only failing to watch session.json is an error.
+fn watch_files(watches: &mut WatchSet<Input>, config: &Config, config_file: &Path) -> Result<()> {
+    watches.retain(|_, input| matches!(input, Input::Image(_)));
+    let options = WatchOptions::from_config(&config.general);
+
+    info!("Starting file watcher for: {}", config.dms.session_file);
+    watches.add_with_options(config.session_file_path(), Input::Session, debounce(config), options)?;
+
+    // settings.json carries the matugen scheme; it may not exist yet
+    match watches.add_with_options(config.settings_file_path(), Input::Settings, debounce(config), options) {
+        Ok(()) => info!("Starting file watcher for: {}", config.dms.settings_file),
+        Err(e) => warn!("Not watching settings file {}: {}", config.dms.settings_file, e),
+    }
+
+    match watches.add_with_options(config_file, Input::Config, debounce(config), options) {
+        Ok(()) => debug!("Watching config file {}", config_file.display()),
+        Err(e) => warn!("Not watching config file {}: {}", config_file.display(), e),
+    }
+    Ok(())
+}
+
+/// Watch the image files `state` shows, and stop watching ones it no longer does
+///
+/// Images are watched with inotify only, through one watcher that watches each
+/// of their directories once: polling would read every file in their
+/// directories on each scan.
+fn watch_images(watches: &mut WatchSet<Input>, state: &WallpaperChange, config: &Config) {
+    let images: BTreeSet<PathBuf> = state
+        .wallpapers
+        .iter()
+        .filter(|w| w.kind == WallpaperKind::Image)
+        .map(|w| PathBuf::from(&w.path))
+        .collect();
+
+    watches.retain(|path, input| !matches!(input, Input::Image(_)) || images.contains(path));
+
+    for image in images {
+        if watches.contains(&image) {
+            continue;
+        }
+        if let Err(e) = watches.add_shared(&image, Input::Image(image.clone()), debounce(config)) {
+            debug!("Not watching {}: {}", image.display(), e);
+        }
+    }
+}
+
+/// What the main loop tracks about DMS's files between events
+struct DmsFiles {
+    /// session.json
+    path: PathBuf,
+    /// Whether session.json is gone; the wallpaper is kept until it reappears
+    missing: bool,
+    /// Recheck for session.json after a deletion, in case its re-creation was debounced away
+    recheck: Option<Instant>,
+    /// How long after a deletion to recheck
+    recheck_after: Duration,
+    /// Drops session.json/settings.json writes that don't touch the wallpaper state
+    filter: ChangeFilter,
+}
 
-                // Retry after delay for non-critical errors
-                tokio::time::sleep(Duration::from_secs(5)).await;
+impl DmsFiles {
+    /// Track the files `config` names, starting from the state read into `snapshot`
+    fn new(config: &Config, snapshot: &Result<DmsSnapshot>) -> Self {
+        let mut filter = ChangeFilter::new();
+        if let Ok(snapshot) = snapshot {
+            filter.observe(snapshot.fingerprint());
+        }
+
+        let path = config.session_file_path();
+        Self {
+            missing: !path.exists(),
+            path,
+            recheck: None,
+            recheck_after: Duration::from_millis(config.general.debounce_ms),
+            filter,
+        }
+    }
+
+    /// Whether session.json exists, logging when it goes away or comes back
+    fn present(&mut self) -> bool {
+        if !self.path.exists() {
+            if !self.missing {
+                warn!(
+                    "{} was removed, keeping the current wallpaper until it reappears",
+                    self.path.display()
+                );
+                self.missing = true;
+                self.recheck = Some(Instant::now() + self.recheck_after);
             }
+            return false;
+        }
+
+        if self.missing {
+            info!("{} is back, re-syncing wallpaper", self.path.display());
+            self.missing = false;
+            self.recheck = None;
+        }
+        true
+    }
+
+    /// Whether `snapshot` changed the wallpaper-relevant state
+    ///
+    /// State that can't be read counts as changed, so the apply reports why.
+    fn changed(&mut self, snapshot: &Result<DmsSnapshot>) -> bool {
+        match snapshot {
+            Ok(snapshot) => self.filter.observe(snapshot.fingerprint()),
+            Err(_) => true,
+        }
+    }
+}
+
+/// Re-read DMS's files and apply them if the wallpaper state changed
+///
+/// Shared by session.json and settings.json events and the recheck after
+/// session.json was deleted. Returns only critical errors.
+async fn sync_dms(
+    session: &Arc<DmsSession>,
+    files: &mut DmsFiles,
+    daemon_rx: &Option<watch::Receiver<DaemonStatus>>,
+    pending: &mut bool,
+    pipeline: &mut ApplyPipeline,
+    last_state: &Option<WallpaperChange>,
+    cycler: &mut Cycler,
+) -> Result<()> {
+    if !files.present() {
+        return Ok(());
+    }
+
+    let snapshot = session.load().await;
+    if !files.changed(&snapshot) {
+        return Ok(());
+    }
+
+    if !daemon_ready(daemon_rx) {
+        info!("awww-daemon not ready, queueing wallpaper apply");
+        *pending = true;
+        return Ok(());
+    }
+
+    if let Err(e) = start_apply(session, snapshot, pipeline, last_state, cycler, false) {
+        error!("Failed to apply wallpaper: {}", e);
+
+        if e.is_critical() {
+            return Err(e);
         }
     }
+    Ok(())
+}
+
+/// Sleep until `deadline`, or forever without one
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Pick the next image for each due cycling target
+///
+/// Returns whether any target got a new image.
+fn advance_cycling(session: &DmsSession, snapshot: &DmsSnapshot, cycler: &mut Cycler, due: &[String]) -> Result<bool> {
+    let dms_state = session.state_of(snapshot)?;
+    let mut shown = dms_state.clone();
+    cycler.apply_overrides(&mut shown);
+
+    let mut advanced = false;
+    for target in due {
+        advanced |= cycler.advance(&shown, &dms_state, target);
+    }
+    Ok(advanced)
 }
 
-/// Apply wallpaper once (check for change and apply)
-async fn apply_wallpaper_once(session: &DmsSession, executor: &Executor) -> Result<()> {
-    // Get current wallpaper state
-    let state = session.get_current_state()?;
+/// Start applying the state read into `snapshot`, superseding any apply in flight
+fn start_apply(
+    session: &DmsSession,
+    snapshot: Result<DmsSnapshot>,
+    pipeline: &mut ApplyPipeline,
+    last_state: &Option<WallpaperChange>,
+    cycler: &mut Cycler,
+    startup: bool,
+) -> Result<()> {
+    // Get current wallpaper state, with cycled images in place of DMS's
+    let snapshot = snapshot?;
+    let mut state = session.state_of(&snapshot)?;
+    cycler.update(cycling::settings_from_session(&snapshot.session));
+    cycler.apply_overrides(&mut state);
 
     info!(
-        "Wallpaper state: {} wallpaper(s), light mode: {}",
-        state.wallpapers.len(), state.is_light_mode
+        "Wallpaper state: {} wallpaper(s), light mode: {}, scheme: {}",
+        state.wallpapers.len(), state.is_light_mode, state.matugen_scheme
     );
 
-    // Apply the wallpaper
-    executor.apply_wallpaper(&state).await?;
+    // Only the stages and monitors that differ from the last applied state run
+    let changes = match last_state {
+        Some(prev) if !startup => ChangeSet::between(prev, &state),
+        _ => ChangeSet::full(last_state.as_ref(), &state),
+    };
+
+    // Unrelated settings.json edits produce events too
+    if changes.is_empty() && !pipeline.is_running() {
+        info!("Wallpaper state unchanged, nothing to apply");
+        return Ok(());
+    }
+
+    debug!("Changes: {:?}", changes);
+
+    let trigger = if startup {
+        ApplyTrigger::Startup
+    } else if changes.mode_changed {
+        ApplyTrigger::ModeChange
+    } else {
+        ApplyTrigger::Change
+    };
+
+    // Apply the wallpaper in the background
+    pipeline.start(state, changes, trigger);
 
     Ok(())
 }
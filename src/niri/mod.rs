@@ -204,6 +204,30 @@ impl NiriClient {
         let outputs = serde_json::from_slice(&output.stdout)?;
         Ok(outputs)
     }
+
+    /// Name of the focused output, if any
+    pub async fn focused_output() -> Result<Option<String>> {
+        let output = AsyncCommand::new("niri")
+            .args(["msg", "--json", "focused-output"])
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(DmsAwwwError::NiriIpc(format!(
+                "niri msg focused-output failed: {}",
+                stderr
+            )));
+        }
+
+        parse_focused_output(&output.stdout)
+    }
+}
+
+/// Parse `niri msg --json focused-output` (`null` when no output is focused)
+fn parse_focused_output(stdout: &[u8]) -> Result<Option<String>> {
+    let output: Option<NiriOutput> = serde_json::from_slice(stdout)?;
+    Ok(output.map(|o| o.name))
 }
 
 /// Parse niri outputs from JSON format (newer niri)
@@ -321,4 +345,12 @@ mod tests {
         assert!(outputs[0].enabled);
         assert_eq!(outputs[0].make, "Dell");
     }
+
+    #[test]
+    fn test_parse_focused_output() {
+        let json = br#"{"name": "DP-1", "make": "LG", "model": "27GL850", "logical": null}"#;
+        assert_eq!(parse_focused_output(json).unwrap(), Some("DP-1".to_string()));
+        assert_eq!(parse_focused_output(b"null").unwrap(), None);
+        assert!(parse_focused_output(b"not json").is_err());
+    }
 }
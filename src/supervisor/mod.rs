@@ -0,0 +1,300 @@
+//! awww-daemon supervision
+//!
+//! This module probes awww-daemon with `awww query`, optionally starts and
+//! restarts it, and publishes its readiness so wallpaper applies can wait
+//! until the daemon is able to accept them.
+
+use crate::config::{AwwwDaemonConfig, Config};
+use crate::error::{DmsAwwwError, Result};
+use std::process::Stdio;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+/// Interval between probes while waiting for a freshly started daemon
+const STARTUP_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay before restarting a managed daemon that exited
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest `awww query` may take before the daemon counts as not answering
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness of awww-daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonStatus {
+    /// Not probed yet
+    Unknown,
+    /// Daemon answers `awww query`
+    Ready,
+    /// Daemon is not running or not answering
+    Down,
+}
+
+/// Supervisor that tracks (and optionally manages) awww-daemon
+pub struct AwwwSupervisor {
+    config: AwwwDaemonConfig,
+    extra_args: Vec<String>,
+    status_tx: watch::Sender<DaemonStatus>,
+    child: Option<Child>,
+}
+
+impl AwwwSupervisor {
+    /// Create a new supervisor from the awww configuration
+    pub fn new(config: &Config) -> Self {
+        let (status_tx, _) = watch::channel(DaemonStatus::Unknown);
+        Self {
+            config: config.awww.daemon.clone(),
+            extra_args: config.awww.extra_args.clone(),
+            status_tx,
+            child: None,
+        }
+    }
+
+    /// Subscribe to daemon status updates
+    pub fn subscribe(&self) -> watch::Receiver<DaemonStatus> {
+        self.status_tx.subscribe()
+    }
+
+    /// Run the supervisor in a background task and return its status receiver
+    pub fn spawn(self) -> watch::Receiver<DaemonStatus> {
+        let rx = self.subscribe();
+        tokio::spawn(self.run());
+        rx
+    }
+
+    /// Check whether awww-daemon answers `awww query` within `PROBE_TIMEOUT`
+    pub async fn probe(extra_args: &[String]) -> bool {
+        let status = Command::new("awww")
+            .args(extra_args)
+            .arg("query")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status();
+
+        match timeout(PROBE_TIMEOUT, status).await {
+            Ok(Ok(status)) => status.success(),
+            Ok(Err(_)) => false,
+            Err(_) => {
+                tracing::warn!("awww query did not answer within {}ms", PROBE_TIMEOUT.as_millis());
+                false
+            }
+        }
+    }
+
+    /// Wait until the daemon reports ready, up to `dur`
+    pub async fn wait_ready(rx: &mut watch::Receiver<DaemonStatus>, dur: Duration) -> Result<()> {
+        match timeout(dur, rx.wait_for(|s| *s == DaemonStatus::Ready)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(_)) => Err(DmsAwwwError::DaemonUnavailable(
+                "supervisor stopped".to_string(),
+            )),
+            Err(_) => Err(DmsAwwwError::DaemonUnavailable(format!(
+                "not ready after {}ms",
+                dur.as_millis()
+            ))),
+        }
+    }
+
+    /// Supervision loop
+    ///
+    /// Ends once every status receiver is dropped. A managed daemon is killed
+    /// with the supervisor, so it never outlives dms-awww; after a reload the
+    /// next supervisor starts its own.
+    async fn run(mut self) {
+        let interval = Duration::from_millis(self.config.probe_interval_ms);
+
+        loop {
+            if self.status_tx.is_closed() {
+                tracing::debug!("awww-daemon supervisor no longer needed, stopping");
+                return;
+            }
+
+            if Self::probe(&self.extra_args).await {
+                self.set_status(DaemonStatus::Ready);
+            } else {
+                self.set_status(DaemonStatus::Down);
+
+                if self.config.manage && self.child.is_none() {
+                    match self.start_daemon() {
+                        Ok(()) => self.wait_for_startup().await,
+                        Err(e) => tracing::error!("Failed to start awww-daemon: {}", e),
+                    }
+                }
+            }
+
+            // Sleep until the next probe, waking early if our daemon exits or we're no longer needed
+            match self.child.as_mut() {
+                Some(child) => {
+                    tokio::select! {
+                        status = child.wait() => {
+                            tracing::warn!("awww-daemon exited: {:?}", status);
+                            self.daemon_exited().await;
+                        }
+                        _ = self.status_tx.closed() => {}
+                        _ = sleep(interval) => {}
+                    }
+                }
+                None => sleep(interval).await,
+            }
+        }
+    }
+
+    /// Start awww-daemon as a child process
+    fn start_daemon(&mut self) -> Result<()> {
+        tracing::info!("Starting awww-daemon");
+
+        self.child = Some(Self::daemon_command("awww-daemon", &self.config.args).spawn()?);
+        Ok(())
+    }
+
+    /// Command for a managed daemon, killed when the supervisor drops it
+    fn daemon_command(program: &str, args: &[String]) -> Command {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true);
+        command
+    }
+
+    /// Probe a freshly started daemon until it answers or the ready timeout passes
+    async fn wait_for_startup(&mut self) {
+        let deadline = Instant::now() + Duration::from_millis(self.config.ready_timeout_ms);
+
+        while Instant::now() < deadline {
+            if let Some(Ok(Some(status))) = self.child.as_mut().map(|c| c.try_wait()) {
+                tracing::warn!("awww-daemon exited during startup: {}", status);
+                self.daemon_exited().await;
+                return;
+            }
+
+            if Self::probe(&self.extra_args).await {
+                tracing::info!("awww-daemon is ready");
+                self.set_status(DaemonStatus::Ready);
+                return;
+            }
+
+            sleep(STARTUP_PROBE_INTERVAL).await;
+        }
+
+        tracing::warn!(
+            "awww-daemon did not become ready within {}ms",
+            self.config.ready_timeout_ms
+        );
+    }
+
+    /// Forget a managed daemon that exited and apply the restart policy
+    ///
+    /// Without `restart` the daemon is no longer managed; otherwise the next
+    /// start waits for `RESTART_DELAY`.
+    async fn daemon_exited(&mut self) {
+        self.child = None;
+        self.set_status(DaemonStatus::Down);
+
+        if !self.config.restart {
+            tracing::warn!("awww-daemon restart is disabled");
+            self.config.manage = false;
+        } else {
+            sleep(RESTART_DELAY).await;
+        }
+    }
+
+    /// Publish a status, notifying subscribers only when it changes
+    fn set_status(&self, status: DaemonStatus) {
+        self.status_tx.send_if_modified(|current| {
+            if *current == status {
+                false
+            } else {
+                tracing::debug!("awww-daemon status: {:?} -> {:?}", current, status);
+                *current = status;
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_ready() {
+        let (tx, mut rx) = watch::channel(DaemonStatus::Down);
+
+        let result = AwwwSupervisor::wait_ready(&mut rx, Duration::from_millis(10)).await;
+        assert!(matches!(result, Err(DmsAwwwError::DaemonUnavailable(_))));
+
+        tx.send(DaemonStatus::Ready).unwrap();
+        AwwwSupervisor::wait_ready(&mut rx, Duration::from_millis(10)).await.unwrap();
+    }
+
+    #[test]
+    fn test_set_status_notifies_on_change_only() {
+        let supervisor = AwwwSupervisor::new(&Config::default());
+        let mut rx = supervisor.subscribe();
+
+        supervisor.set_status(DaemonStatus::Unknown);
+        assert!(!rx.has_changed().unwrap());
+
+        supervisor.set_status(DaemonStatus::Ready);
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), DaemonStatus::Ready);
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_stops_without_subscribers() {
+        let supervisor = AwwwSupervisor::new(&Config::default());
+        let rx = supervisor.subscribe();
+        let handle = tokio::spawn(supervisor.run());
+
+        drop(rx);
+        timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_kills_managed_daemon_when_stopping() {
+        let mut supervisor = AwwwSupervisor::new(&Config::default());
+        let child = AwwwSupervisor::daemon_command("sleep", &["30".to_string()]).spawn().unwrap();
+        let pid = child.id().unwrap();
+        supervisor.child = Some(child);
+
+        let rx = supervisor.subscribe();
+        let handle = tokio::spawn(supervisor.run());
+        drop(rx);
+        timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        // Gone, or a zombie waiting to be reaped
+        let stat = format!("/proc/{pid}/stat");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match std::fs::read_to_string(&stat) {
+                Ok(stat) if !stat.contains(") Z ") => {}
+                _ => break,
+            }
+            assert!(Instant::now() < deadline, "managed daemon outlived its supervisor");
+            sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_daemon_exited_restart_policy() {
+        let mut config = Config::default();
+        config.awww.daemon.manage = true;
+        config.awww.daemon.restart = true;
+
+        let mut supervisor = AwwwSupervisor::new(&config);
+        let start = Instant::now();
+        supervisor.daemon_exited().await;
+        assert!(start.elapsed() >= RESTART_DELAY);
+        assert!(supervisor.config.manage);
+
+        // Without restart, a daemon that died (during startup too) is left alone
+        supervisor.config.restart = false;
+        supervisor.daemon_exited().await;
+        assert!(!supervisor.config.manage);
+        assert_eq!(*supervisor.subscribe().borrow(), DaemonStatus::Down);
+    }
+}
@@ -0,0 +1,86 @@
+//! DMS theme generator (default, `dms matugen queue`)
+
+use super::{ThemeBackend, ThemeRequest, ThemeSource};
+use crate::backend::{run_command, BackendFuture};
+use crate::config::MatugenConfig;
+
+/// Generator that queues matugen through the DMS CLI
+///
+/// DMS renders its own templates and reloads the shell afterwards.
+pub struct DmsTheme {
+    config: MatugenConfig,
+    cache_dir: String,
+}
+
+impl DmsTheme {
+    /// Create a new DMS generator writing its state to `cache_dir`
+    pub fn new(config: MatugenConfig, cache_dir: String) -> Self {
+        Self { config, cache_dir }
+    }
+
+    /// Build the `dms` argument list for a request
+    fn args(&self, request: &ThemeRequest) -> Vec<String> {
+        let config_dir = std::env::var("HOME")
+            .map(|h| format!("{}/.config", h))
+            .unwrap_or_else(|_| "~/.config".to_string());
+
+        let (kind, value) = match &request.source {
+            ThemeSource::Image(path) => ("image", path.as_str()),
+            ThemeSource::Color(color) => ("color", color.as_str()),
+        };
+
+        [
+            "matugen", "queue",
+            "--state-dir", &self.cache_dir,
+            "--shell-dir", &self.config.shell_dir,
+            "--config-dir", &config_dir,
+            "--kind", kind,
+            "--value", value,
+            "--mode", request.mode(),
+            "--matugen-type", &request.scheme,
+            "--wait",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+}
+
+impl ThemeBackend for DmsTheme {
+    fn name(&self) -> &'static str {
+        "dms"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["dms"]
+    }
+
+    fn generate<'a>(&'a self, request: &'a ThemeRequest) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            run_command("dms", &self.args(request)).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dms_args() {
+        let theme = DmsTheme::new(MatugenConfig::default(), "/cache".to_string());
+        let request = ThemeRequest {
+            source: ThemeSource::Color("#1e1e2e".to_string()),
+            is_light_mode: true,
+            scheme: "scheme-content".to_string(),
+        };
+
+        let args = theme.args(&request);
+        assert_eq!(&args[..4], ["matugen", "queue", "--state-dir", "/cache"]);
+        assert!(args.windows(2).any(|w| w == ["--kind", "color"]));
+        assert!(args.windows(2).any(|w| w == ["--value", "#1e1e2e"]));
+        assert!(args.windows(2).any(|w| w == ["--mode", "light"]));
+        assert!(args.windows(2).any(|w| w == ["--matugen-type", "scheme-content"]));
+    }
+}
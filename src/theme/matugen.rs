@@ -0,0 +1,85 @@
+//! Direct matugen theme generator (`matugen image` / `matugen color hex`)
+
+use super::{ThemeBackend, ThemeRequest, ThemeSource};
+use crate::backend::{run_command, BackendFuture};
+use crate::config::MatugenCliConfig;
+
+/// Generator that runs matugen without going through DMS
+///
+/// Renders the templates in the user's own matugen config, so it works
+/// without the DMS CLI.
+pub struct MatugenTheme {
+    config: MatugenCliConfig,
+}
+
+impl MatugenTheme {
+    /// Create a new matugen generator
+    pub fn new(config: MatugenCliConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the `matugen` argument list for a request
+    fn args(&self, request: &ThemeRequest) -> Vec<String> {
+        let mut args = match &request.source {
+            ThemeSource::Image(path) => vec!["image".to_string(), path.clone()],
+            ThemeSource::Color(color) => vec!["color".to_string(), "hex".to_string(), color.clone()],
+        };
+
+        args.extend(["--mode".to_string(), request.mode().to_string()]);
+        args.extend(["--type".to_string(), request.scheme.clone()]);
+        if let Some(config_file) = &self.config.config_file {
+            args.extend(["--config".to_string(), config_file.clone()]);
+        }
+        args.extend(self.config.extra_args.iter().cloned());
+        args
+    }
+}
+
+impl ThemeBackend for MatugenTheme {
+    fn name(&self) -> &'static str {
+        "matugen"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["matugen"]
+    }
+
+    fn generate<'a>(&'a self, request: &'a ThemeRequest) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            run_command("matugen", &self.args(request)).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matugen_args() {
+        let theme = MatugenTheme::new(MatugenCliConfig {
+            config_file: Some("/etc/matugen.toml".to_string()),
+            extra_args: vec!["--quiet".to_string()],
+        });
+
+        let image = ThemeRequest {
+            source: ThemeSource::Image("/tmp/a.png".to_string()),
+            is_light_mode: false,
+            scheme: "scheme-tonal-spot".to_string(),
+        };
+        assert_eq!(
+            theme.args(&image),
+            vec![
+                "image", "/tmp/a.png", "--mode", "dark", "--type", "scheme-tonal-spot",
+                "--config", "/etc/matugen.toml", "--quiet",
+            ]
+        );
+
+        let color = ThemeRequest {
+            source: ThemeSource::Color("#1e1e2e".to_string()),
+            ..image
+        };
+        assert_eq!(&theme.args(&color)[..3], ["color", "hex", "#1e1e2e"]);
+    }
+}
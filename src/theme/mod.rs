@@ -0,0 +1,116 @@
+//! Theme generators
+//!
+//! This module defines the `ThemeBackend` trait used by the executor to
+//! generate color themes from the current wallpaper, along with the DMS
+//! (default), matugen, wallust and pywal implementations selected by
+//! `[theme] kind`.
+
+mod dms;
+mod matugen;
+mod pywal;
+mod wallust;
+
+pub use dms::DmsTheme;
+pub use matugen::MatugenTheme;
+pub use pywal::PywalTheme;
+pub use wallust::WallustTheme;
+
+use crate::backend::BackendFuture;
+use crate::config::{Config, ThemeKind};
+use crate::dms::Wallpaper;
+use std::sync::Arc;
+
+/// What a theme is generated from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThemeSource {
+    /// Image file path
+    Image(String),
+    /// Solid color as `#rrggbb`
+    Color(String),
+}
+
+impl ThemeSource {
+    /// Theme source for a wallpaper
+    pub fn from_wallpaper(wallpaper: &Wallpaper) -> Self {
+        match wallpaper.color() {
+            Some(color) => ThemeSource::Color(color),
+            None => ThemeSource::Image(wallpaper.path.clone()),
+        }
+    }
+}
+
+/// A single theme generation request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeRequest {
+    /// Image or color to derive colors from
+    pub source: ThemeSource,
+    /// Light mode if true, dark mode otherwise
+    pub is_light_mode: bool,
+    /// Material scheme type (e.g. `scheme-tonal-spot`); ignored by generators without schemes
+    pub scheme: String,
+}
+
+impl ThemeRequest {
+    /// `light` or `dark`
+    pub fn mode(&self) -> &'static str {
+        if self.is_light_mode {
+            "light"
+        } else {
+            "dark"
+        }
+    }
+}
+
+/// A program that generates color themes from a wallpaper
+pub trait ThemeBackend: Send + Sync {
+    /// Short name used in logs and errors
+    fn name(&self) -> &'static str;
+
+    /// Commands that must be on PATH for this generator
+    fn required_commands(&self) -> Vec<&'static str>;
+
+    /// Generate and apply a theme
+    fn generate<'a>(&'a self, request: &'a ThemeRequest) -> BackendFuture<'a, ()>;
+}
+
+/// Create the theme generator selected by `[theme] kind`
+pub fn create_theme_backend(config: &Config) -> Arc<dyn ThemeBackend> {
+    match config.theme.kind {
+        ThemeKind::Dms => Arc::new(DmsTheme::new(config.matugen.clone(), config.dms.cache_dir.clone())),
+        ThemeKind::Matugen => Arc::new(MatugenTheme::new(config.theme.matugen.clone())),
+        ThemeKind::Wallust => Arc::new(WallustTheme::new(config.theme.wallust.clone())),
+        ThemeKind::Pywal => Arc::new(PywalTheme::new(config.theme.pywal.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_source_from_wallpaper() {
+        assert_eq!(
+            ThemeSource::from_wallpaper(&Wallpaper::new("/tmp/a.png".to_string())),
+            ThemeSource::Image("/tmp/a.png".to_string())
+        );
+        assert_eq!(
+            ThemeSource::from_wallpaper(&Wallpaper::new("#1E1E2E".to_string())),
+            ThemeSource::Color("#1E1E2E".to_string())
+        );
+    }
+
+    #[test]
+    fn test_create_theme_backend_kind() {
+        let mut config = Config::default();
+        assert_eq!(create_theme_backend(&config).name(), "dms");
+
+        config.theme.kind = ThemeKind::Matugen;
+        assert_eq!(create_theme_backend(&config).name(), "matugen");
+
+        config.theme.kind = ThemeKind::Wallust;
+        assert_eq!(create_theme_backend(&config).name(), "wallust");
+
+        config.theme.kind = ThemeKind::Pywal;
+        assert_eq!(create_theme_backend(&config).name(), "pywal");
+    }
+}
@@ -0,0 +1,71 @@
+//! pywal theme generator (`wal -i`)
+
+use super::{ThemeBackend, ThemeRequest, ThemeSource};
+use crate::backend::{run_command, unsupported, BackendFuture};
+use crate::config::PywalConfig;
+
+/// Generator that runs pywal on the wallpaper image
+///
+/// `-n` is always passed so pywal leaves the wallpaper to the wallpaper backend.
+pub struct PywalTheme {
+    config: PywalConfig,
+}
+
+impl PywalTheme {
+    /// Create a new pywal generator
+    pub fn new(config: PywalConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the `wal` argument list for an image
+    fn args(&self, path: &str, is_light_mode: bool) -> Vec<String> {
+        let mut args = vec!["-i".to_string(), path.to_string(), "-n".to_string()];
+        if is_light_mode {
+            args.push("-l".to_string());
+        }
+        if let Some(backend) = &self.config.backend {
+            args.extend(["--backend".to_string(), backend.clone()]);
+        }
+        args.extend(self.config.extra_args.iter().cloned());
+        args
+    }
+}
+
+impl ThemeBackend for PywalTheme {
+    fn name(&self) -> &'static str {
+        "pywal"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["wal"]
+    }
+
+    fn generate<'a>(&'a self, request: &'a ThemeRequest) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            match &request.source {
+                ThemeSource::Image(path) => {
+                    run_command("wal", &self.args(path, request.is_light_mode)).await?;
+                    Ok(())
+                }
+                ThemeSource::Color(_) => Err(unsupported("pywal", "solid colors")),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pywal_args() {
+        let theme = PywalTheme::new(PywalConfig {
+            backend: Some("colorz".to_string()),
+            extra_args: vec![],
+        });
+        assert_eq!(
+            theme.args("/tmp/a.png", true),
+            vec!["-i", "/tmp/a.png", "-n", "-l", "--backend", "colorz"]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! wallust theme generator (`wallust run`)
+
+use super::{ThemeBackend, ThemeRequest, ThemeSource};
+use crate::backend::{run_command, unsupported, BackendFuture};
+use crate::config::WallustConfig;
+
+/// Generator that runs `wallust run` on the wallpaper image
+///
+/// wallust has no material schemes; light/dark picks the palette instead.
+pub struct WallustTheme {
+    config: WallustConfig,
+}
+
+impl WallustTheme {
+    /// Create a new wallust generator
+    pub fn new(config: WallustConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the `wallust` argument list for an image
+    fn args(&self, path: &str, is_light_mode: bool) -> Vec<String> {
+        let palette = if is_light_mode {
+            &self.config.light_palette
+        } else {
+            &self.config.dark_palette
+        };
+
+        let mut args = vec!["run".to_string(), path.to_string()];
+        args.extend(["--palette".to_string(), palette.clone()]);
+        if let Some(backend) = &self.config.backend {
+            args.extend(["--backend".to_string(), backend.clone()]);
+        }
+        args.extend(self.config.extra_args.iter().cloned());
+        args
+    }
+}
+
+impl ThemeBackend for WallustTheme {
+    fn name(&self) -> &'static str {
+        "wallust"
+    }
+
+    fn required_commands(&self) -> Vec<&'static str> {
+        vec!["wallust"]
+    }
+
+    fn generate<'a>(&'a self, request: &'a ThemeRequest) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            match &request.source {
+                ThemeSource::Image(path) => {
+                    run_command("wallust", &self.args(path, request.is_light_mode)).await?;
+                    Ok(())
+                }
+                ThemeSource::Color(_) => Err(unsupported("wallust", "solid colors")),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallust_args() {
+        let theme = WallustTheme::new(WallustConfig::default());
+        assert_eq!(
+            theme.args("/tmp/a.png", false),
+            vec!["run", "/tmp/a.png", "--palette", "dark16"]
+        );
+        assert_eq!(
+            theme.args("/tmp/a.png", true),
+            vec!["run", "/tmp/a.png", "--palette", "light16"]
+        );
+    }
+}
@@ -1,13 +1,29 @@
 //! File system watcher using inotify
 //!
 //! This module provides event-driven file system monitoring using the notify crate,
-//! allowing near-instant detection of changes without polling.
+//! allowing near-instant detection of changes without polling. notify's
+//! callback thread hands events to an async channel, so no runtime worker is
+//! blocked waiting for them.
+//!
+//! Where inotify events never arrive (NFS, some container bind mounts) the
+//! `poll` backend scans the watched directory instead; `auto` starts with
+//! inotify and switches to polling once a change goes unreported.
+//!
+//! `WatchSet` puts several watched files, each with its own debounce, on
+//! one stream of tagged events.
+
+mod set;
 
+pub use set::WatchSet;
+
+use crate::config::{GeneralConfig, WatchBackend};
 use crate::error::DmsAwwwError;
-use notify::{Event, EventKind, RecursiveMode, RecommendedWatcher, Watcher};
-use std::path::Path;
+use notify::{Event, EventKind, PollWatcher, RecursiveMode, RecommendedWatcher, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tokio::sync::mpsc;
-use tokio::time::{timeout, Duration};
+use tokio::time::{timeout, Duration, Instant, MissedTickBehavior};
 
 /// File change event
 #[derive(Debug, Clone)]
@@ -22,42 +38,170 @@ pub enum FileEvent {
     Error(String),
 }
 
+/// How files are watched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchOptions {
+    /// Watching mechanism
+    pub backend: WatchBackend,
+    /// Scan interval for polling, and how often `auto` checks inotify
+    pub poll_interval: Duration,
+}
+
+impl WatchOptions {
+    /// Options from `[general] watch_backend` and `poll_interval_ms`
+    pub fn from_config(general: &GeneralConfig) -> Self {
+        Self {
+            backend: general.watch_backend,
+            poll_interval: Duration::from_millis(general.poll_interval_ms),
+        }
+    }
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self::from_config(&GeneralConfig::default())
+    }
+}
+
 /// File watcher that monitors a file for changes
 pub struct FileWatcher {
-    _watcher: RecommendedWatcher,
+    _watcher: Arc<Mutex<Box<dyn Watcher + Send>>>,
     rx: mpsc::Receiver<FileEvent>,
     path: std::path::PathBuf,
 }
 
+/// Closest existing directory on the way to `dir` (`dir` itself if it exists)
+fn nearest_existing(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|p| p.is_dir())
+        .unwrap_or_else(|| Path::new("/"))
+        .to_path_buf()
+}
+
+/// Create a notify watcher that forwards its events to `ntx`
+fn create_watcher(
+    poll: bool,
+    ntx: mpsc::UnboundedSender<Event>,
+    poll_interval: Duration,
+) -> std::result::Result<Box<dyn Watcher + Send>, DmsAwwwError> {
+    let handler = move |res: std::result::Result<Event, notify::Error>| {
+        if let Ok(event) = res {
+            if let Err(e) = ntx.send(event) {
+                tracing::error!("Failed to send notify event: {}", e);
+            }
+        }
+    };
+
+    let watcher: Box<dyn Watcher + Send> = if poll {
+        // mtimes are only compared to the second, so hash contents to catch quick rewrites
+        let config = notify::Config::default()
+            .with_poll_interval(poll_interval)
+            .with_compare_contents(true);
+        Box::new(PollWatcher::new(handler, config).map_err(|e| DmsAwwwError::Watcher(e.to_string()))?)
+    } else {
+        Box::new(
+            RecommendedWatcher::new(handler, notify::Config::default())
+                .map_err(|e| DmsAwwwError::Watcher(e.to_string()))?,
+        )
+    };
+    Ok(watcher)
+}
+
+/// The file event a notify event kind means, if any
+fn file_event(kind: &EventKind) -> Option<FileEvent> {
+    match kind {
+        EventKind::Create(_) => Some(FileEvent::Created),
+        EventKind::Modify(_) => Some(FileEvent::Modified),
+        EventKind::Remove(_) => Some(FileEvent::Deleted),
+        EventKind::Any => Some(FileEvent::Modified),
+        _ => None,
+    }
+}
+
+/// What `auto` compares between checks: the watched directory and the file's size and mtime
+type Stamp = (PathBuf, Option<(u64, Option<SystemTime>)>);
+
+fn stamp(target: &Path, parent: &Path) -> Stamp {
+    let file = std::fs::metadata(target).ok().map(|m| (m.len(), m.modified().ok()));
+    (nearest_existing(parent), file)
+}
+
+/// Notices inotify not reporting changes, for the `auto` backend
+#[derive(Debug)]
+struct SilenceProbe {
+    stamp: Stamp,
+    seen: u64,
+    unreported: bool,
+}
+
+impl SilenceProbe {
+    fn new(stamp: Stamp) -> Self {
+        Self { stamp, seen: 0, unreported: false }
+    }
+
+    /// Compare a periodic check with the previous one; true once a change was missed
+    ///
+    /// `seen` counts the notify events received so far. A change with no event
+    /// since the previous check may still have its event in flight, so it only
+    /// counts as missed if the following interval is silent too.
+    fn missed(&mut self, seen: u64, stamp: Stamp) -> bool {
+        let silent = seen == self.seen;
+        let changed = stamp != self.stamp;
+        self.seen = seen;
+        self.stamp = stamp;
+
+        if !silent {
+            self.unreported = false;
+            return false;
+        }
+        if self.unreported {
+            return true;
+        }
+        self.unreported = changed;
+        false
+    }
+}
+
 impl FileWatcher {
-    /// Create a new file watcher for the specified path
+    /// Create a new file watcher for the specified path, with the default options
+    ///
+    /// The file and its parent directory don't have to exist yet: the
+    /// nearest existing ancestor is watched until the directory appears.
     pub fn new<P: AsRef<Path>>(path: P) -> std::result::Result<Self, DmsAwwwError> {
+        Self::with_options(path, WatchOptions::default())
+    }
+
+    /// Create a new file watcher using the given backend
+    pub fn with_options<P: AsRef<Path>>(path: P, options: WatchOptions) -> std::result::Result<Self, DmsAwwwError> {
         let path = path.as_ref().to_path_buf();
         let (tx, rx) = mpsc::channel(32);
 
-        // Create a channel for notify events
-        let (ntx, nrx) = std::sync::mpsc::channel();
+        // Create a channel for notify events; sending never blocks notify's thread
+        let (ntx, mut nrx) = mpsc::unbounded_channel();
 
         // Watch the parent directory since the file might be replaced
-        let watch_path = path
+        let parent = path
             .parent()
             .unwrap_or_else(|| Path::new("/"))
             .to_path_buf();
+        let mut watch_path = nearest_existing(&parent);
+        if watch_path != parent {
+            tracing::info!(
+                "{} does not exist yet, watching {} until it appears",
+                parent.display(),
+                watch_path.display()
+            );
+        }
 
-        let mut watcher = RecommendedWatcher::new(
-            move |res: std::result::Result<Event, notify::Error>| {
-                if let Ok(event) = res {
-                    if let Err(e) = ntx.send(event) {
-                        tracing::error!("Failed to send notify event: {}", e);
-                    }
-                }
-            },
-            notify::Config::default(),
-        ).map_err(|e| DmsAwwwError::Watcher(e.to_string()))?;
+        // `auto` keeps a way to start a poll watcher without keeping the channel open
+        let fallback_tx = ntx.downgrade();
+        let mut watcher = create_watcher(options.backend == WatchBackend::Poll, ntx, options.poll_interval)?;
 
         watcher.watch(&watch_path, RecursiveMode::NonRecursive)
             .map_err(|e| DmsAwwwError::Watcher(e.to_string()))?;
 
+        let watcher = Arc::new(Mutex::new(watcher));
+
         // Spawn a task to bridge notify events to our channel
         let target_filename = path
             .file_name()
@@ -67,8 +211,95 @@ impl FileWatcher {
             ))?
             .to_string();
 
+        let target = path.clone();
+        let rewatch = Arc::downgrade(&watcher);
+        let mut probing = options.backend == WatchBackend::Auto;
         tokio::spawn(async move {
-            while let Ok(event) = nrx.recv() {
+            let mut probe = tokio::time::interval(options.poll_interval);
+            probe.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut silence = SilenceProbe::new(stamp(&target, &parent));
+            let mut seen = 0u64;
+
+            loop {
+                let event = tokio::select! {
+                    event = nrx.recv() => match event {
+                        Some(event) => event,
+                        None => break, // FileWatcher dropped
+                    },
+                    _ = probe.tick(), if probing => {
+                        if !silence.missed(seen, stamp(&target, &parent)) {
+                            continue;
+                        }
+
+                        let (Some(watcher), Some(ntx)) = (rewatch.upgrade(), fallback_tx.upgrade()) else {
+                            break; // FileWatcher dropped
+                        };
+                        tracing::warn!(
+                            "inotify missed a change to {}, polling every {}ms instead",
+                            target.display(),
+                            options.poll_interval.as_millis()
+                        );
+                        probing = false;
+
+                        watch_path = nearest_existing(&parent);
+                        match create_watcher(true, ntx, options.poll_interval) {
+                            Ok(mut poll) => {
+                                if let Err(e) = poll.watch(&watch_path, RecursiveMode::NonRecursive) {
+                                    tracing::error!("Failed to watch {}: {}", watch_path.display(), e);
+                                }
+                                *watcher.lock().unwrap_or_else(|e| e.into_inner()) = poll;
+                            }
+                            Err(e) => tracing::error!("Failed to start polling: {}", e),
+                        }
+
+                        // Report the change inotify dropped
+                        if tx.send(FileEvent::Modified).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                seen += 1;
+
+                // A directory on the way to the file was created or removed
+                let on_path = event
+                    .paths
+                    .iter()
+                    .any(|p| p != &target && target.starts_with(p));
+                if on_path && matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_)) {
+                    let Some(watcher) = rewatch.upgrade() else {
+                        break; // FileWatcher dropped
+                    };
+                    let previous = watch_path.clone();
+                    {
+                        let mut watcher = watcher.lock().unwrap_or_else(|e| e.into_inner());
+
+                        // Deeper directories may appear before their parent's watch is in place
+                        loop {
+                            let next = nearest_existing(&parent);
+                            if next == watch_path {
+                                break;
+                            }
+                            let _ = watcher.unwatch(&watch_path);
+                            if let Err(e) = watcher.watch(&next, RecursiveMode::NonRecursive) {
+                                tracing::error!("Failed to watch {}: {}", next.display(), e);
+                            }
+                            tracing::debug!("Now watching {} for {}", next.display(), target.display());
+                            watch_path = next;
+                        }
+                    }
+
+                    // The file may have been written before the watch was in place
+                    if watch_path != previous
+                        && watch_path == parent
+                        && target.exists()
+                        && tx.send(FileEvent::Created).await.is_err()
+                    {
+                        break;
+                    }
+                    continue;
+                }
+
                 // Check if the event is for our target file
                 let is_target = event.paths.iter().any(|p: &std::path::PathBuf| {
                     p.file_name()
@@ -81,12 +312,8 @@ impl FileWatcher {
                     continue;
                 }
 
-                let file_event = match event.kind {
-                    EventKind::Create(_) => FileEvent::Created,
-                    EventKind::Modify(_) => FileEvent::Modified,
-                    EventKind::Remove(_) => FileEvent::Deleted,
-                    EventKind::Any => FileEvent::Modified,
-                    _ => continue,
+                let Some(file_event) = file_event(&event.kind) else {
+                    continue;
                 };
 
                 if tx.send(file_event).await.is_err() {
@@ -129,49 +356,137 @@ impl FileWatcher {
     }
 }
 
+/// Trailing-edge debounce over a stream of file events
+///
+/// A burst of events is reported once, `debounce` after its last event, so
+/// the final write of the burst has landed. A burst that never goes quiet is
+/// still reported `max_wait` after its first event.
+#[derive(Debug)]
+pub struct Debounce {
+    debounce: Duration,
+    max_wait: Duration,
+    pending: Option<PendingEvent>,
+}
+
+/// Latest event of a burst that hasn't been reported yet
+#[derive(Debug)]
+struct PendingEvent {
+    event: FileEvent,
+    first: Instant,
+    last: Instant,
+}
+
+impl Debounce {
+    /// Create a debounce; `max_wait` is raised to `debounce` if shorter
+    pub fn new(debounce: Duration, max_wait: Duration) -> Self {
+        Self {
+            debounce,
+            max_wait: max_wait.max(debounce),
+            pending: None,
+        }
+    }
+
+    /// Wait for the next burst in `rx` and return its last event
+    ///
+    /// Returns None once `rx` is closed and nothing is pending. Cancel-safe:
+    /// a pending burst is kept across calls, so it can be used in `select!`.
+    pub async fn next(&mut self, rx: &mut mpsc::Receiver<FileEvent>) -> Option<FileEvent> {
+        loop {
+            let deadline = self
+                .pending
+                .as_ref()
+                .map(|p| (p.last + self.debounce).min(p.first + self.max_wait));
+
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event) => {
+                        let now = Instant::now();
+                        let first = self.pending.as_ref().map_or(now, |p| p.first);
+                        self.pending = Some(PendingEvent { event, first, last: now });
+                    }
+                    None => return self.pending.take().map(|p| p.event),
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    return self.pending.take().map(|p| p.event);
+                }
+            }
+        }
+    }
+}
+
 /// Debounced file watcher that coalesces rapid changes
 pub struct DebouncedWatcher {
     watcher: FileWatcher,
-    debounce_ms: u64,
-    last_event_time: Option<tokio::time::Instant>,
+    debounce: Debounce,
 }
 
 impl DebouncedWatcher {
     /// Create a new debounced watcher
-    pub fn new<P: AsRef<Path>>(path: P, debounce_ms: u64) -> std::result::Result<Self, DmsAwwwError> {
+    ///
+    /// Events are reported `debounce_ms` after the last one of a burst, or
+    /// `max_wait_ms` after the first if the burst keeps going.
+    pub fn new<P: AsRef<Path>>(path: P, debounce_ms: u64, max_wait_ms: u64) -> std::result::Result<Self, DmsAwwwError> {
+        Self::with_options(path, debounce_ms, max_wait_ms, WatchOptions::default())
+    }
+
+    /// Create a new debounced watcher using the given backend
+    pub fn with_options<P: AsRef<Path>>(
+        path: P,
+        debounce_ms: u64,
+        max_wait_ms: u64,
+        options: WatchOptions,
+    ) -> std::result::Result<Self, DmsAwwwError> {
         Ok(Self {
-            watcher: FileWatcher::new(path)?,
-            debounce_ms,
-            last_event_time: None,
+            watcher: FileWatcher::with_options(path, options)?,
+            debounce: Debounce::new(Duration::from_millis(debounce_ms), Duration::from_millis(max_wait_ms)),
         })
     }
 
     /// Wait for the next debounced event
+    ///
+    /// Cancel-safe, so it can be used in `select!`.
     pub async fn next(&mut self) -> std::result::Result<Option<FileEvent>, DmsAwwwError> {
-        loop {
-            let event = match self.watcher.next().await {
-                Some(e) => e,
-                None => return Ok(None),
-            };
-
-            let now = tokio::time::Instant::now();
-
-            if let Some(last_time) = self.last_event_time {
-                let elapsed = now.duration_since(last_time).as_millis() as u64;
-
-                if elapsed >= self.debounce_ms {
-                    self.last_event_time = Some(now);
-                    return Ok(Some(event));
-                } else {
-                    // Wait for debounce period
-                    tokio::time::sleep(Duration::from_millis(self.debounce_ms - elapsed)).await;
-                    continue;
-                }
-            } else {
-                self.last_event_time = Some(now);
-                return Ok(Some(event));
-            }
+        Ok(self.debounce.next(&mut self.watcher.rx).await)
+    }
+}
+
+/// Drops file events that didn't change the content that matters
+///
+/// Callers pass a fingerprint (hash) of the relevant content after each
+/// event; repeats of the last fingerprint are no-op writes.
+#[derive(Debug, Default)]
+pub struct ChangeFilter {
+    last: Option<u64>,
+    passed: u64,
+    dropped: u64,
+}
+
+impl ChangeFilter {
+    /// Create a filter that passes the first fingerprint
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `fingerprint` and tell whether it differs from the last one
+    pub fn observe(&mut self, fingerprint: u64) -> bool {
+        let changed = self.last != Some(fingerprint);
+        self.last = Some(fingerprint);
+
+        if changed {
+            self.passed += 1;
+        } else {
+            self.dropped += 1;
+            tracing::debug!(
+                "Dropped no-op file event ({} dropped, {} passed so far)",
+                self.dropped, self.passed
+            );
         }
+        changed
+    }
+
+    /// Number of events dropped as no-ops
+    pub fn dropped(&self) -> u64 {
+        self.dropped
     }
 }
 
@@ -193,4 +508,145 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_file_watcher_waits_for_missing_parent() -> std::result::Result<(), DmsAwwwError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let test_file = temp_dir.path().join("state/DankMaterialShell/session.json");
+
+        let mut watcher = FileWatcher::new(&test_file)?;
+
+        std::fs::create_dir_all(test_file.parent().unwrap())?;
+        std::fs::write(&test_file, b"{}")?;
+
+        let event = watcher.next_with_timeout(Duration::from_secs(5)).await?;
+        assert!(matches!(event, Some(FileEvent::Created | FileEvent::Modified)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_filter_drops_repeats() {
+        let mut filter = ChangeFilter::new();
+        assert!(filter.observe(1));
+        assert!(!filter.observe(1));
+        assert!(!filter.observe(1));
+        assert!(filter.observe(2));
+        assert!(filter.observe(1));
+        assert_eq!(filter.dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_poll_watcher_detects_changes() -> std::result::Result<(), DmsAwwwError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let test_file = temp_dir.path().join("session.json");
+        std::fs::write(&test_file, b"{}")?;
+
+        let options = WatchOptions {
+            backend: WatchBackend::Poll,
+            poll_interval: Duration::from_millis(20),
+        };
+        let mut watcher = FileWatcher::with_options(&test_file, options)?;
+
+        // Let the first scan record the file before it changes
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::write(&test_file, br#"{"wallpaperPath": "/tmp/a.jpg"}"#)?;
+
+        let event = watcher.next_with_timeout(Duration::from_secs(5)).await?;
+        assert!(matches!(event, Some(FileEvent::Modified | FileEvent::Created)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_silence_probe() {
+        let dir = PathBuf::from("/state");
+        let at = |len| (dir.clone(), Some((len, None)));
+        let mut probe = SilenceProbe::new(at(1));
+
+        // Changes with events, or with the event arriving in the next interval
+        assert!(!probe.missed(1, at(2)));
+        assert!(!probe.missed(1, at(3)));
+        assert!(!probe.missed(2, at(3)));
+
+        // Quiet file
+        assert!(!probe.missed(2, at(3)));
+
+        // A change and then an interval without any event
+        assert!(!probe.missed(2, at(4)));
+        assert!(probe.missed(2, at(4)));
+    }
+
+    /// Send `events` into a channel, sleeping the given delay before each
+    fn send_events(events: Vec<(u64, FileEvent)>) -> mpsc::Receiver<FileEvent> {
+        let (tx, rx) = mpsc::channel(32);
+        tokio::spawn(async move {
+            for (delay, event) in events {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                tx.send(event).await.unwrap();
+            }
+            // Keep the channel open so only the timers decide
+            std::future::pending::<()>().await;
+        });
+        rx
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounce_fires_after_last_event() {
+        let start = Instant::now();
+        let mut rx = send_events(vec![
+            (0, FileEvent::Modified),
+            (60, FileEvent::Deleted),
+            (60, FileEvent::Created),
+        ]);
+        let mut debounce = Debounce::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        // The burst's last event, 100ms after it arrived at 120ms
+        let event = debounce.next(&mut rx).await;
+        assert!(matches!(event, Some(FileEvent::Created)));
+        assert_eq!(start.elapsed(), Duration::from_millis(220));
+
+        // Nothing else was lost or left pending
+        let next = timeout(Duration::from_secs(5), debounce.next(&mut rx)).await;
+        assert!(next.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounce_max_wait() {
+        let start = Instant::now();
+        let mut rx = send_events((0..100).map(|_| (40, FileEvent::Modified)).collect());
+        let mut debounce = Debounce::new(Duration::from_millis(100), Duration::from_millis(500));
+
+        // Events keep coming every 40ms; each burst is cut off 500ms after it began
+        assert!(debounce.next(&mut rx).await.is_some());
+        assert_eq!(start.elapsed(), Duration::from_millis(540));
+        assert!(debounce.next(&mut rx).await.is_some());
+        assert_eq!(start.elapsed(), Duration::from_millis(1060));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounce_is_cancel_safe() {
+        let start = Instant::now();
+        let mut rx = send_events(vec![(0, FileEvent::Modified), (50, FileEvent::Created)]);
+        let mut debounce = Debounce::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        // Dropping `next` mid-burst keeps what was received
+        let cancelled = timeout(Duration::from_millis(75), debounce.next(&mut rx)).await;
+        assert!(cancelled.is_err());
+
+        let event = debounce.next(&mut rx).await;
+        assert!(matches!(event, Some(FileEvent::Created)));
+        assert_eq!(start.elapsed(), Duration::from_millis(150));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounce_flushes_on_close() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let mut debounce = Debounce::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        tx.send(FileEvent::Modified).await.unwrap();
+        drop(tx);
+        assert!(matches!(debounce.next(&mut rx).await, Some(FileEvent::Modified)));
+        assert!(debounce.next(&mut rx).await.is_none());
+    }
 }
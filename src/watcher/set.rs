@@ -0,0 +1,393 @@
+//! Several watched files on one event stream
+//!
+//! Each path gets its own debounce, and its own watcher unless it is added
+//! to the shared one; its events come out tagged, so one `select!` branch can
+//! route them.
+
+use super::{file_event, Debounce, FileEvent, FileWatcher, WatchOptions};
+use crate::error::DmsAwwwError;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// A watched path, its tag and the task forwarding its events
+struct Watched<T> {
+    path: PathBuf,
+    tag: T,
+    task: JoinHandle<()>,
+    /// Route in the shared watcher, if the path is watched through it
+    shared: Option<Route>,
+}
+
+/// Per-file event channels of the shared watcher, keyed by canonical path
+type Routes = Arc<Mutex<HashMap<PathBuf, Vec<mpsc::Sender<FileEvent>>>>>;
+
+/// One file's channel in the shared watcher
+struct Route {
+    /// Canonical path of the file
+    path: PathBuf,
+    tx: mpsc::Sender<FileEvent>,
+}
+
+/// `path` with symlinks, `.` and `..` resolved, as far as it exists
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => std::fs::canonicalize(dir)
+            .map(|dir| dir.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// One inotify watcher for many files, watching each directory once
+///
+/// Watching every file's directory with its own watcher would use an inotify
+/// instance per file and run into `max_user_instances`. Files are matched by
+/// canonical path, so different spellings of one file all get its events.
+struct SharedWatcher {
+    watcher: RecommendedWatcher,
+    /// Watched directories and how many files are watched in each
+    dirs: HashMap<PathBuf, usize>,
+    routes: Routes,
+}
+
+impl SharedWatcher {
+    fn new() -> Result<Self, DmsAwwwError> {
+        let routes = Routes::default();
+        let handler_routes = Arc::clone(&routes);
+        let handler = move |res: notify::Result<Event>| {
+            let Ok(event) = res else { return };
+            let Some(file_event) = file_event(&event.kind) else { return };
+            let routes = handler_routes.lock().unwrap_or_else(|e| e.into_inner());
+            for path in &event.paths {
+                // Paths come from the canonical directories watched, so the lookup rarely misses
+                let senders = routes.get(path).or_else(|| routes.get(&canonical(path)));
+                for tx in senders.into_iter().flatten() {
+                    // A full channel already has events waiting for the debounce
+                    let _ = tx.try_send(file_event.clone());
+                }
+            }
+        };
+        let watcher = RecommendedWatcher::new(handler, notify::Config::default())
+            .map_err(|e| DmsAwwwError::Watcher(e.to_string()))?;
+
+        Ok(Self {
+            watcher,
+            dirs: HashMap::new(),
+            routes,
+        })
+    }
+
+    /// Route events for `path` to the returned channel, watching its directory if needed
+    fn add(&mut self, path: &Path) -> Result<(Route, mpsc::Receiver<FileEvent>), DmsAwwwError> {
+        let path = canonical(path);
+        let dir = path
+            .parent()
+            .ok_or_else(|| DmsAwwwError::Watcher(format!("{} has no parent directory", path.display())))?;
+        if !self.dirs.contains_key(dir) {
+            self.watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| DmsAwwwError::Watcher(e.to_string()))?;
+        }
+        *self.dirs.entry(dir.to_path_buf()).or_default() += 1;
+
+        let (tx, rx) = mpsc::channel(32);
+        self.routes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(path.clone())
+            .or_default()
+            .push(tx.clone());
+        Ok((Route { path, tx }, rx))
+    }
+
+    /// Stop routing events for `route`, unwatching its directory once nothing else is in it
+    fn remove(&mut self, route: &Route) {
+        {
+            let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(senders) = routes.get_mut(&route.path) {
+                senders.retain(|tx| !tx.same_channel(&route.tx));
+                if senders.is_empty() {
+                    routes.remove(&route.path);
+                }
+            }
+        }
+
+        let Some(dir) = route.path.parent() else { return };
+        let Some(count) = self.dirs.get_mut(dir) else { return };
+        *count -= 1;
+        if *count == 0 {
+            self.dirs.remove(dir);
+            if let Err(e) = self.watcher.unwatch(dir) {
+                tracing::debug!("Failed to unwatch {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+/// Set of watched files yielding `(tag, event)` pairs
+pub struct WatchSet<T> {
+    options: WatchOptions,
+    tx: mpsc::Sender<(T, FileEvent)>,
+    rx: mpsc::Receiver<(T, FileEvent)>,
+    watched: Vec<Watched<T>>,
+    /// Created on the first `add_shared`
+    shared: Option<SharedWatcher>,
+}
+
+impl<T: Clone + Send + 'static> WatchSet<T> {
+    /// Create an empty set; paths are watched with `options` unless given their own
+    pub fn new(options: WatchOptions) -> Self {
+        let (tx, rx) = mpsc::channel(32);
+        Self {
+            options,
+            tx,
+            rx,
+            watched: Vec::new(),
+            shared: None,
+        }
+    }
+
+    /// Watch `path`, reporting its debounced events tagged with `tag`
+    pub fn add<P: AsRef<Path>>(&mut self, path: P, tag: T, debounce: Debounce) -> Result<(), DmsAwwwError> {
+        self.add_with_options(path, tag, debounce, self.options)
+    }
+
+    /// Watch `path` with its own backend options
+    ///
+    /// A path that is already watched is replaced.
+    pub fn add_with_options<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        tag: T,
+        mut debounce: Debounce,
+        options: WatchOptions,
+    ) -> Result<(), DmsAwwwError> {
+        let path = path.as_ref().to_path_buf();
+        let watcher = FileWatcher::with_options(&path, options)?;
+        self.remove(&path);
+
+        let tx = self.tx.clone();
+        let task_tag = tag.clone();
+        let task = tokio::spawn(async move {
+            // Move the whole watcher in, not just `rx`; dropping it (on abort) stops watching
+            let mut watcher = watcher;
+            while let Some(event) = debounce.next(&mut watcher.rx).await {
+                if tx.send((task_tag.clone(), event)).await.is_err() {
+                    break; // WatchSet dropped
+                }
+            }
+        });
+
+        self.watched.push(Watched {
+            path,
+            tag,
+            task,
+            shared: None,
+        });
+        Ok(())
+    }
+
+    /// Watch `path` through one inotify watcher shared by every path added this way
+    ///
+    /// Each directory is watched once, however many of its files are watched.
+    /// Unlike `add`, the directory must exist and there is no polling fallback.
+    /// A path that is already watched is replaced.
+    pub fn add_shared<P: AsRef<Path>>(&mut self, path: P, tag: T, mut debounce: Debounce) -> Result<(), DmsAwwwError> {
+        let path = path.as_ref().to_path_buf();
+        self.remove(&path);
+
+        let shared = match &mut self.shared {
+            Some(shared) => shared,
+            empty => empty.insert(SharedWatcher::new()?),
+        };
+        let (route, mut rx) = shared.add(&path)?;
+
+        let tx = self.tx.clone();
+        let task_tag = tag.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = debounce.next(&mut rx).await {
+                if tx.send((task_tag.clone(), event)).await.is_err() {
+                    break; // WatchSet dropped
+                }
+            }
+        });
+
+        self.watched.push(Watched {
+            path,
+            tag,
+            task,
+            shared: Some(route),
+        });
+        Ok(())
+    }
+
+    /// Stop watching `path`; returns whether it was watched
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let before = self.watched.len();
+        self.retain(|watched, _| watched != path);
+        self.watched.len() != before
+    }
+
+    /// Keep watching only the paths for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path, &T) -> bool) {
+        let shared = &mut self.shared;
+        self.watched.retain(|w| {
+            let kept = keep(&w.path, &w.tag);
+            if !kept {
+                w.task.abort();
+                if let (Some(route), Some(shared)) = (&w.shared, shared.as_mut()) {
+                    shared.remove(route);
+                }
+            }
+            kept
+        });
+    }
+
+    /// Whether `path` is watched
+    pub fn contains(&self, path: &Path) -> bool {
+        self.watched.iter().any(|w| w.path == path)
+    }
+
+    /// Watched paths, in the order they were added
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.watched.iter().map(|w| w.path.as_path())
+    }
+
+    /// Wait for the next event from any watched path
+    ///
+    /// Waits forever while nothing is watched. Cancel-safe, so it can be used
+    /// in `select!`.
+    pub async fn next(&mut self) -> Option<(T, FileEvent)> {
+        self.rx.recv().await
+    }
+}
+
+impl<T> Drop for WatchSet<T> {
+    fn drop(&mut self) {
+        for watched in &self.watched {
+            watched.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WatchBackend;
+    use std::time::Duration;
+
+    fn debounce() -> Debounce {
+        Debounce::new(Duration::from_millis(20), Duration::from_millis(200))
+    }
+
+    #[tokio::test]
+    async fn test_watch_set_tags_events() -> Result<(), DmsAwwwError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let session = temp_dir.path().join("session.json");
+        let image = temp_dir.path().join("images/wall.png");
+        std::fs::create_dir_all(image.parent().unwrap())?;
+
+        let options = WatchOptions {
+            backend: WatchBackend::Inotify,
+            poll_interval: Duration::from_secs(1),
+        };
+        let mut set = WatchSet::new(options);
+        set.add(&session, "session", debounce())?;
+        set.add(&image, "image", debounce())?;
+        assert!(set.contains(&image));
+
+        std::fs::write(&image, b"new content")?;
+        let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+        assert_eq!(tag, "image");
+
+        std::fs::write(&session, b"{}")?;
+        let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+        assert_eq!(tag, "session");
+
+        // Removed paths go quiet
+        set.retain(|_, tag| *tag != "image");
+        assert!(!set.remove(&image));
+        std::fs::write(&image, b"newer content")?;
+        assert!(tokio::time::timeout(Duration::from_millis(300), set.next()).await.is_err());
+        assert_eq!(set.paths().collect::<Vec<_>>(), [session.as_path()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_watcher_watches_each_directory_once() -> Result<(), DmsAwwwError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let first = temp_dir.path().join("first.png");
+        let second = temp_dir.path().join("second.png");
+        let other_dir = temp_dir.path().join("other");
+        std::fs::create_dir_all(&other_dir)?;
+        let third = other_dir.join("third.png");
+
+        let mut set = WatchSet::new(WatchOptions::default());
+        set.add_shared(&first, "first", debounce())?;
+        set.add_shared(&second, "second", debounce())?;
+        set.add_shared(&third, "third", debounce())?;
+        assert_eq!(set.shared.as_ref().unwrap().dirs.len(), 2);
+
+        // Events are routed by path, and unwatched files in the directory stay quiet
+        std::fs::write(temp_dir.path().join("unrelated.png"), b"content")?;
+        std::fs::write(&second, b"content")?;
+        let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+        assert_eq!(tag, "second");
+        assert!(tokio::time::timeout(Duration::from_millis(300), set.next()).await.is_err());
+
+        // A directory is unwatched with its last file
+        assert!(set.remove(&third));
+        assert_eq!(set.shared.as_ref().unwrap().dirs.len(), 1);
+        set.remove(&second);
+        assert_eq!(set.shared.as_ref().unwrap().dirs.get(&temp_dir.path().canonicalize()?), Some(&1));
+
+        std::fs::write(&second, b"new content")?;
+        std::fs::write(&first, b"new content")?;
+        let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+        assert_eq!(tag, "first");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_watcher_matches_non_canonical_paths() -> Result<(), DmsAwwwError> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let real_dir = temp_dir.path().join("real");
+        std::fs::create_dir_all(&real_dir)?;
+        let link_dir = temp_dir.path().join("link");
+        std::os::unix::fs::symlink(&real_dir, &link_dir)?;
+        let file = real_dir.join("wall.png");
+        std::fs::write(&file, b"content")?;
+
+        let mut set = WatchSet::new(WatchOptions::default());
+        set.add_shared(link_dir.join("wall.png"), "link", debounce())?;
+        set.add_shared(real_dir.join("..").join("real").join("wall.png"), "dotdot", debounce())?;
+        assert_eq!(set.shared.as_ref().unwrap().dirs.len(), 1);
+
+        // Both spellings get the event
+        std::fs::write(&file, b"new content")?;
+        let mut tags = Vec::new();
+        for _ in 0..2 {
+            let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+            tags.push(tag);
+        }
+        tags.sort();
+        assert_eq!(tags, ["dotdot", "link"]);
+
+        // Removing one spelling keeps the other routed
+        assert!(set.remove(&link_dir.join("wall.png")));
+        std::fs::write(&file, b"newer content")?;
+        let (tag, _) = tokio::time::timeout(Duration::from_secs(5), set.next()).await.unwrap().unwrap();
+        assert_eq!(tag, "dotdot");
+
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 //! Common test utilities for dms-awww integration tests
 
+#![allow(dead_code)]
+
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -11,6 +13,11 @@ pub struct SessionFixture {
     per_monitor_wallpaper: Option<bool>,
     monitor_wallpapers: Vec<(String, String)>,
     is_light_mode: Option<bool>,
+    per_mode_wallpaper: Option<bool>,
+    wallpaper_path_light: Option<String>,
+    wallpaper_path_dark: Option<String>,
+    monitor_wallpapers_light: Vec<(String, String)>,
+    monitor_wallpapers_dark: Vec<(String, String)>,
 }
 
 impl Default for SessionFixture {
@@ -20,6 +27,11 @@ impl Default for SessionFixture {
             per_monitor_wallpaper: Some(false),
             monitor_wallpapers: Vec::new(),
             is_light_mode: Some(false),
+            per_mode_wallpaper: None,
+            wallpaper_path_light: None,
+            wallpaper_path_dark: None,
+            monitor_wallpapers_light: Vec::new(),
+            monitor_wallpapers_dark: Vec::new(),
         }
     }
 }
@@ -54,6 +66,38 @@ impl SessionFixture {
         self
     }
 
+    /// Enable per-mode (light/dark) wallpaper mode
+    pub fn per_mode(mut self, enabled: bool) -> Self {
+        self.per_mode_wallpaper = Some(enabled);
+        self
+    }
+
+    /// Set the wallpaper path for light or dark mode
+    pub fn mode_wallpaper_path(mut self, light: bool, path: impl Into<String>) -> Self {
+        if light {
+            self.wallpaper_path_light = Some(path.into());
+        } else {
+            self.wallpaper_path_dark = Some(path.into());
+        }
+        self
+    }
+
+    /// Add a monitor wallpaper for light or dark mode
+    pub fn mode_monitor_wallpaper(
+        mut self,
+        light: bool,
+        monitor: impl Into<String>,
+        path: impl Into<String>,
+    ) -> Self {
+        let entry = (monitor.into(), path.into());
+        if light {
+            self.monitor_wallpapers_light.push(entry);
+        } else {
+            self.monitor_wallpapers_dark.push(entry);
+        }
+        self
+    }
+
     /// Build the session JSON string
     pub fn build_json(&self) -> String {
         let mut json = String::from("{");
@@ -64,14 +108,14 @@ impl SessionFixture {
 
         if let Some(pmw) = self.per_monitor_wallpaper {
             if !json.is_empty() && json != "{" {
-                json.push_str(",");
+                json.push(',');
             }
             json.push_str(&format!("\"perMonitorWallpaper\":{}", if pmw { "true" } else { "false" }));
         }
 
         if !self.monitor_wallpapers.is_empty() {
             if !json.is_empty() && json != "{" {
-                json.push_str(",");
+                json.push(',');
             }
             json.push_str("\"monitorWallpapers\":{");
             let wallpapers: Vec<String> = self.monitor_wallpapers
@@ -79,17 +123,52 @@ impl SessionFixture {
                 .map(|(m, p)| format!("\"{}\":\"{}\"", m, p))
                 .collect();
             json.push_str(&wallpapers.join(","));
-            json.push_str("}");
+            json.push('}');
         }
 
         if let Some(lm) = self.is_light_mode {
             if !json.is_empty() && json != "{" {
-                json.push_str(",");
+                json.push(',');
             }
             json.push_str(&format!("\"isLightMode\":{}", if lm { "true" } else { "false" }));
         }
 
-        json.push_str("}");
+        if let Some(pmw) = self.per_mode_wallpaper {
+            if !json.is_empty() && json != "{" {
+                json.push(',');
+            }
+            json.push_str(&format!("\"perModeWallpaper\":{}", if pmw { "true" } else { "false" }));
+        }
+
+        for (key, path) in [
+            ("wallpaperPathLight", &self.wallpaper_path_light),
+            ("wallpaperPathDark", &self.wallpaper_path_dark),
+        ] {
+            if let Some(path) = path {
+                if !json.is_empty() && json != "{" {
+                    json.push(',');
+                }
+                json.push_str(&format!("\"{}\":\"{}\"", key, path));
+            }
+        }
+
+        for (key, monitors) in [
+            ("monitorWallpapersLight", &self.monitor_wallpapers_light),
+            ("monitorWallpapersDark", &self.monitor_wallpapers_dark),
+        ] {
+            if !monitors.is_empty() {
+                if !json.is_empty() && json != "{" {
+                    json.push(',');
+                }
+                let wallpapers: Vec<String> = monitors
+                    .iter()
+                    .map(|(m, p)| format!("\"{}\":\"{}\"", m, p))
+                    .collect();
+                json.push_str(&format!("\"{}\":{{{}}}", key, wallpapers.join(",")));
+            }
+        }
+
+        json.push('}');
         json
     }
 
@@ -139,12 +218,12 @@ impl SettingsFixture {
 
         for (key, value) in &self.other_fields {
             if !json.is_empty() && json != "{" {
-                json.push_str(",");
+                json.push(',');
             }
             json.push_str(&format!("\"{}\":\"{}\"", key, value));
         }
 
-        json.push_str("}");
+        json.push('}');
         json
     }
 
@@ -7,7 +7,8 @@ use std::fs;
 use serial_test::serial;
 
 // Import from the main crate
-use dms_awww::config::Config;
+use dms_awww::config::{ApplyOrder, BackendKind, Config, HookEvent, ThemeKind, ThemeSourcePolicy, WatchBackend};
+use dms_awww::error::DmsAwwwError;
 
 /// Helper to clear all DMS_AWWW environment variables
 fn clear_env_vars() {
@@ -20,8 +21,15 @@ fn clear_env_vars() {
         "DMS_AWWW_CACHE_DIR",
         "DMS_AWWW_NIRI_OUTPUTS",
         "DMS_AWWW_AWWW_ENABLED",
+        "DMS_AWWW_TRANSITION_TYPE",
+        "DMS_AWWW_BACKEND",
+        "DMS_AWWW_THEME",
+        "DMS_AWWW_CYCLING_ENABLED",
+        "DMS_AWWW_IMAGE_CACHE_MAX_MB",
+        "DMS_AWWW_WATCH_BACKEND",
         "DMS_AWWW_MATUGEN_ENABLED",
         "DMS_AWWW_MATUGEN_SCHEME",
+        "DMS_AWWW_MATUGEN_SOURCE",
         "DMS_AWWW_SHELL_DIR",
         "XDG_CONFIG_HOME",
     ];
@@ -38,7 +46,7 @@ fn test_config_load_default_values() {
 
     // Should return defaults
     assert_eq!(config.general.log_level, "info");
-    assert_eq!(config.general.auto_detect_monitors, true);
+    assert!(config.general.auto_detect_monitors);
     assert!(config.awww.enabled);
     assert!(config.matugen.enabled);
 }
@@ -87,7 +95,7 @@ shell_dir = "/tmp/shell"
 
     assert_eq!(config.general.log_level, "debug");
     assert_eq!(config.general.log_file, "/tmp/test.log");
-    assert_eq!(config.general.auto_detect_monitors, false);
+    assert!(!config.general.auto_detect_monitors);
     assert_eq!(config.general.debounce_ms, 200);
     assert_eq!(config.dms.session_file, "/tmp/session.json");
     assert!(!config.awww.enabled);
@@ -134,6 +142,310 @@ matugen:
     clear_env_vars();
 }
 
+#[serial]
+#[test]
+fn test_config_load_rejects_invalid_types() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "[general]\ndebounce_ms = \"soon\"\n").unwrap();
+
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // A reload must be able to tell a broken file from an empty one
+    let result = Config::load();
+    assert!(matches!(result, Err(DmsAwwwError::ConfigFile(_))));
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_from_explicit_path() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    // Found by XDG lookup, but an explicit path wins
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "[matugen]\nenabled = true\n").unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let explicit = temp_dir.path().join("custom.conf");
+    fs::write(&explicit, "[matugen]\nenabled = false\n").unwrap();
+
+    assert!(Config::load().unwrap().matugen.enabled);
+    assert!(!Config::load_from(Some(&explicit)).unwrap().matugen.enabled);
+    assert_eq!(Config::config_file_path(Some(&explicit)), explicit);
+    assert_eq!(Config::config_file_path(None), config_dir.join("config.toml"));
+
+    // A missing explicit file is an error, not the defaults
+    let missing = temp_dir.path().join("missing.toml");
+    assert!(Config::load_from(Some(&missing)).is_err());
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_awww_transitions() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config_file = config_dir.join("config.toml");
+
+    let toml_content = r#"
+[awww]
+img_args = ["--resize", "fit"]
+
+[awww.transition]
+type = "grow"
+duration = 2
+fps = 144
+position = "top-right"
+
+[awww.monitors.HDMI-A-1]
+img_args = ["--fill-color", "000000"]
+
+[awww.monitors.HDMI-A-1.transition]
+type = "wipe"
+angle = 30
+
+[awww.events.startup]
+type = "simple"
+"#;
+    fs::write(&config_file, toml_content).unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::load().unwrap();
+    config.validate().unwrap();
+
+    assert_eq!(config.awww.img_args, vec!["--resize", "fit"]);
+    assert_eq!(config.awww.transition.transition_type.as_deref(), Some("grow"));
+    assert_eq!(config.awww.transition.duration, Some(2.0));
+    assert_eq!(config.awww.transition.fps, Some(144));
+
+    let hdmi = config.awww.transition_for("HDMI-A-1");
+    assert_eq!(hdmi.transition_type.as_deref(), Some("wipe"));
+    assert_eq!(hdmi.angle, Some(30.0));
+    assert_eq!(hdmi.position.as_deref(), Some("top-right"));
+    assert_eq!(
+        config.awww.img_args_for("HDMI-A-1"),
+        vec!["--resize", "fit", "--fill-color", "000000"]
+    );
+
+    assert_eq!(config.awww.events.startup.transition_type.as_deref(), Some("simple"));
+    // Unspecified events keep their defaults
+    assert_eq!(config.awww.events.mode_change.transition_type.as_deref(), Some("fade"));
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_executor_order() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        "[executor]\norder = \"matugen-first\"\nstage_delay_ms = 250\n",
+    )
+    .unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::load().unwrap();
+    assert_eq!(config.executor.order, ApplyOrder::MatugenFirst);
+    assert_eq!(config.executor.stage_delay_ms, 250);
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_backend() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        "[backend]\nkind = \"swaybg\"\n\n[backend.swaybg]\nmode = \"center\"\n",
+    )
+    .unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::load().unwrap();
+    assert_eq!(config.backend.kind, BackendKind::Swaybg);
+    assert_eq!(config.backend.swaybg.mode, "center");
+    assert!(config.backend.hyprpaper.unload_unused);
+
+    env::set_var("DMS_AWWW_BACKEND", "wpaperd");
+    let config = Config::load().unwrap();
+    assert_eq!(config.backend.kind, BackendKind::Wpaperd);
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_theme() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        "[theme]\nkind = \"wallust\"\n\n[theme.wallust]\ndark_palette = \"harddark\"\n",
+    )
+    .unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::load().unwrap();
+    assert_eq!(config.theme.kind, ThemeKind::Wallust);
+    assert_eq!(config.theme.wallust.dark_palette, "harddark");
+    assert_eq!(config.theme.wallust.light_palette, "light16");
+
+    env::set_var("DMS_AWWW_THEME", "pywal");
+    let config = Config::load().unwrap();
+    assert_eq!(config.theme.kind, ThemeKind::Pywal);
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_load_hooks() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let config_dir = temp_dir.path().join("dms-awww");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        r#"
+[[hooks]]
+on = "after-awww"
+command = "swaylock-update \"$DMS_AWWW_WALLPAPER\""
+
+[[hooks]]
+on = "on-error"
+command = "notify-send dms-awww \"$DMS_AWWW_ERROR\""
+timeout_ms = 2000
+"#,
+    )
+    .unwrap();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let config = Config::load().unwrap();
+    assert_eq!(config.hooks.len(), 2);
+    assert_eq!(config.hooks[0].on, HookEvent::AfterAwww);
+    assert_eq!(config.hooks[0].timeout_ms, 10_000);
+    assert_eq!(config.hooks[1].on, HookEvent::OnError);
+    assert_eq!(config.hooks[1].timeout_ms, 2000);
+    assert!(config.validate().is_ok());
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_cycling_enabled() {
+    clear_env_vars();
+    assert!(Config::load().unwrap().cycling.enabled);
+
+    env::set_var("DMS_AWWW_CYCLING_ENABLED", "false");
+    assert!(!Config::load().unwrap().cycling.enabled);
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_matugen_source() {
+    clear_env_vars();
+    assert_eq!(Config::load().unwrap().matugen.source, ThemeSourcePolicy::Primary);
+
+    for (value, policy) in [
+        ("focused", ThemeSourcePolicy::Focused),
+        ("composite", ThemeSourcePolicy::Composite),
+        ("output:DP-1", ThemeSourcePolicy::Output("DP-1".to_string())),
+    ] {
+        env::set_var("DMS_AWWW_MATUGEN_SOURCE", value);
+        assert_eq!(Config::load().unwrap().matugen.source, policy);
+    }
+
+    // Typos and bare names are ignored rather than taken as output names
+    for value in ["", "primray", "DP-1", "output:"] {
+        env::set_var("DMS_AWWW_MATUGEN_SOURCE", value);
+        assert_eq!(Config::load().unwrap().matugen.source, ThemeSourcePolicy::Primary, "{value}");
+    }
+
+    let mut config = Config::default();
+    config.matugen.source = ThemeSourcePolicy::Output(String::new());
+    assert!(config.validate().is_err());
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_matugen_source_rejects_unknown_keywords() {
+    clear_env_vars();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.toml");
+
+    fs::write(&config_file, "[matugen]\nsource = \"output:HDMI-A-1\"\n").unwrap();
+    assert_eq!(
+        Config::load_from(Some(&config_file)).unwrap().matugen.source,
+        ThemeSourcePolicy::Output("HDMI-A-1".to_string())
+    );
+
+    for value in ["primray", "HDMI-A-1"] {
+        fs::write(&config_file, format!("[matugen]\nsource = \"{value}\"\n")).unwrap();
+        assert!(matches!(Config::load_from(Some(&config_file)), Err(DmsAwwwError::ConfigFile(_))), "{value}");
+    }
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_watch_backend() {
+    clear_env_vars();
+    let config = Config::load().unwrap();
+    assert_eq!(config.general.watch_backend, WatchBackend::Auto);
+    assert_eq!(config.general.poll_interval_ms, 1000);
+
+    env::set_var("DMS_AWWW_WATCH_BACKEND", "poll");
+    assert_eq!(Config::load().unwrap().general.watch_backend, WatchBackend::Poll);
+
+    env::set_var("DMS_AWWW_WATCH_BACKEND", "fanotify");
+    assert_eq!(Config::load().unwrap().general.watch_backend, WatchBackend::Auto);
+
+    clear_env_vars();
+}
+
+#[serial]
+#[test]
+fn test_config_image_cache_max_mb() {
+    clear_env_vars();
+    assert_eq!(Config::load().unwrap().dms.image_cache_max_mb, 512);
+
+    env::set_var("DMS_AWWW_IMAGE_CACHE_MAX_MB", "64");
+    assert_eq!(Config::load().unwrap().dms.image_cache_max_mb, 64);
+
+    clear_env_vars();
+}
+
 #[serial]
 #[test]
 fn test_env_var_override_log_level() {
@@ -5,10 +5,13 @@ mod common;
 use std::fs;
 use std::path::Path;
 
+use common::create_test_image;
 use common::SessionFixture;
 use common::SettingsFixture;
 use dms_awww::config::Config;
-use dms_awww::dms::{DmsSession, SessionJson, SettingsJson, Wallpaper, WallpaperChange};
+use dms_awww::dms::{ChangeSet, DmsSession, SessionJson, SettingsJson, Wallpaper, WallpaperKind};
+use dms_awww::error::DmsAwwwError;
+use dms_awww::image::ImageFormat;
 
 /// Create a test config with paths pointing to a temp directory
 fn test_config_with_dir(dir: &Path) -> Config {
@@ -131,6 +134,28 @@ fn test_wallpaper_exists() {
     assert!(!wp_not_exists.exists());
 }
 
+#[test]
+fn test_wallpaper_validate_keeps_metadata() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.png");
+    create_test_image(&path);
+
+    let mut wallpaper = Wallpaper::new(path.to_str().unwrap().to_string());
+    assert!(wallpaper.image.is_none());
+    wallpaper.validate().unwrap();
+    let image = wallpaper.image.unwrap();
+    assert_eq!(image.format, ImageFormat::Png);
+    assert_eq!((image.width, image.height), (1, 1));
+
+    // Colors are never probed
+    let mut color = Wallpaper::new("#1e1e2e".to_string());
+    assert!(color.validate().is_ok());
+    assert!(color.image.is_none());
+
+    let mut missing = Wallpaper::new(temp_dir.path().join("missing.png").to_str().unwrap().to_string());
+    assert!(matches!(missing.validate(), Err(DmsAwwwError::InvalidImage { .. })));
+}
+
 #[test]
 fn test_wallpaper_for_monitor() {
     let wp = Wallpaper::for_monitor("/path/to/wp.jpg".to_string(), "HDMI-A-1".to_string());
@@ -168,6 +193,27 @@ fn test_dms_session_read_session_not_found() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_dms_session_read_session_last_known_good() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+    let session = DmsSession::new(config);
+
+    // Nothing to fall back to yet
+    fs::write(temp_dir.path().join("session.json"), r#"{"wallpaperPath": "#).unwrap();
+    assert!(session.read_session().is_err());
+
+    SessionFixture::new()
+        .wallpaper_path("/tmp/test.jpg")
+        .write_to(temp_dir.path());
+    assert!(session.read_session().is_ok());
+
+    // A write that never completes keeps the last good session
+    fs::write(temp_dir.path().join("session.json"), r#"{"wallpaperPath": "/tmp/oth"#).unwrap();
+    let session_json = session.read_session().unwrap();
+    assert_eq!(session_json.wallpaper_path, Some("/tmp/test.jpg".to_string()));
+}
+
 #[test]
 fn test_dms_session_read_settings() {
     let temp_dir = tempfile::TempDir::new().unwrap();
@@ -207,7 +253,7 @@ fn test_dms_session_get_current_state_single_wallpaper() {
     let state = state.unwrap();
     assert_eq!(state.wallpapers.len(), 1);
     assert_eq!(state.wallpapers[0].path, "/tmp/test.jpg");
-    assert_eq!(state.is_light_mode, false);
+    assert!(!state.is_light_mode);
 }
 
 #[test]
@@ -230,7 +276,7 @@ fn test_dms_session_get_current_state_per_monitor() {
     assert!(state.is_ok());
     let state = state.unwrap();
     assert_eq!(state.wallpapers.len(), 2);
-    assert_eq!(state.is_light_mode, true);
+    assert!(state.is_light_mode);
 
     // Check that we have both monitors with correct wallpapers
     let hdmi_wp = state.wallpapers.iter()
@@ -245,12 +291,51 @@ fn test_dms_session_get_current_state_per_monitor() {
 }
 
 #[test]
-fn test_dms_session_get_current_state_filters_colors() {
+fn test_dms_session_per_monitor_order_is_deterministic() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+
+    SessionFixture::new()
+        .per_monitor(true)
+        .monitor_wallpaper("eDP-1", "/tmp/wp1.jpg")
+        .monitor_wallpaper("HDMI-A-1", "/tmp/wp2.jpg")
+        .monitor_wallpaper("DP-1", "/tmp/wp3.jpg")
+        .write_to(temp_dir.path());
+
+    let session = DmsSession::new(config);
+    for _ in 0..5 {
+        let state = session.get_current_state().unwrap();
+        let monitors: Vec<_> = state.wallpapers.iter().map(|w| w.monitor.as_deref()).collect();
+        assert_eq!(monitors, [Some("DP-1"), Some("HDMI-A-1"), Some("eDP-1")]);
+    }
+}
+
+#[test]
+fn test_dms_session_get_current_state_keeps_colors() {
     let temp_dir = tempfile::TempDir::new().unwrap();
     let config = test_config_with_dir(temp_dir.path());
 
     SessionFixture::new()
-        .wallpaper_path("#ff0000")
+        .wallpaper_path("/#ff0000")
+        .write_to(temp_dir.path());
+
+    SettingsFixture::new().write_to(temp_dir.path());
+
+    let session = DmsSession::new(config);
+    let state = session.get_current_state().unwrap();
+
+    assert_eq!(state.wallpapers.len(), 1);
+    assert_eq!(state.wallpapers[0].kind, WallpaperKind::Color);
+    assert_eq!(state.wallpapers[0].color(), Some("#ff0000".to_string()));
+}
+
+#[test]
+fn test_dms_session_get_current_state_rejects_malformed_colors() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+
+    SessionFixture::new()
+        .wallpaper_path("#notacolor")
         .write_to(temp_dir.path());
 
     SettingsFixture::new().write_to(temp_dir.path());
@@ -281,7 +366,7 @@ fn test_dms_session_get_current_state_empty_wallpapers() {
 }
 
 #[test]
-fn test_dms_session_has_changed() {
+fn test_dms_session_change_set() {
     let temp_dir = tempfile::TempDir::new().unwrap();
     let config = test_config_with_dir(temp_dir.path());
 
@@ -291,26 +376,130 @@ fn test_dms_session_has_changed() {
 
     SettingsFixture::new().write_to(temp_dir.path());
 
-    let mut session = DmsSession::new(config);
-
-    // First check should return true (initial state)
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(has_changed.unwrap());
+    let session = DmsSession::new(config);
+    let first = session.get_current_state().unwrap();
 
-    // Second check with same file should return false
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(!has_changed.unwrap());
+    // Re-reading the same file yields no changes
+    let second = session.get_current_state().unwrap();
+    assert!(ChangeSet::between(&first, &second).is_empty());
 
     // Modify the file
     SessionFixture::new()
         .wallpaper_path("/tmp/test2.jpg")
+        .light_mode(true)
+        .write_to(temp_dir.path());
+
+    let third = session.get_current_state().unwrap();
+    let changes = ChangeSet::between(&second, &third);
+    assert_eq!(changes.wallpapers_changed, vec!["ALL"]);
+    assert!(changes.mode_changed);
+    assert!(!changes.scheme_changed);
+}
+
+#[test]
+fn test_dms_session_per_mode_wallpapers() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+    SettingsFixture::new().write_to(temp_dir.path());
+    let session = DmsSession::new(config);
+
+    let fixture = SessionFixture::new()
+        .wallpaper_path("/tmp/base.jpg")
+        .per_mode(true)
+        .mode_wallpaper_path(true, "/tmp/light.jpg")
+        .mode_wallpaper_path(false, "/tmp/dark.jpg");
+
+    fixture.write_to(temp_dir.path());
+    let state = session.get_current_state().unwrap();
+    assert_eq!(state.wallpapers, vec![Wallpaper::new("/tmp/dark.jpg".to_string())]);
+
+    // Toggling the mode switches the image
+    fixture.light_mode(true).write_to(temp_dir.path());
+    let state = session.get_current_state().unwrap();
+    assert_eq!(state.wallpapers, vec![Wallpaper::new("/tmp/light.jpg".to_string())]);
+}
+
+#[test]
+fn test_dms_session_per_mode_per_monitor_fallback() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+    SettingsFixture::new().write_to(temp_dir.path());
+    let session = DmsSession::new(config);
+
+    SessionFixture::new()
+        .per_monitor(true)
+        .per_mode(true)
+        .light_mode(true)
+        .monitor_wallpaper("DP-1", "/tmp/dp1.jpg")
+        .monitor_wallpaper("HDMI-A-1", "/tmp/hdmi.jpg")
+        .mode_monitor_wallpaper(true, "DP-1", "/tmp/dp1-light.jpg")
+        .mode_monitor_wallpaper(false, "HDMI-A-1", "/tmp/hdmi-dark.jpg")
+        .write_to(temp_dir.path());
+
+    let mut state = session.get_current_state().unwrap();
+    state.wallpapers.sort_by(|a, b| a.monitor.cmp(&b.monitor));
+    assert_eq!(
+        state.wallpapers,
+        vec![
+            Wallpaper::for_monitor("/tmp/dp1-light.jpg".to_string(), "DP-1".to_string()),
+            // No light variant for this monitor, so the shared one is kept
+            Wallpaper::for_monitor("/tmp/hdmi.jpg".to_string(), "HDMI-A-1".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_dms_session_per_mode_keys_ignored_when_disabled() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+    SettingsFixture::new().write_to(temp_dir.path());
+
+    // Per-mode keys without perModeWallpaper, and per-mode on without keys
+    SessionFixture::new()
+        .wallpaper_path("/tmp/base.jpg")
+        .mode_wallpaper_path(false, "/tmp/dark.jpg")
         .write_to(temp_dir.path());
+    let session = DmsSession::new(config);
+    let state = session.get_current_state().unwrap();
+    assert_eq!(state.wallpapers, vec![Wallpaper::new("/tmp/base.jpg".to_string())]);
+
+    SessionFixture::new()
+        .wallpaper_path("/tmp/base.jpg")
+        .per_mode(true)
+        .write_to(temp_dir.path());
+    let state = session.get_current_state().unwrap();
+    assert_eq!(state.wallpapers, vec![Wallpaper::new("/tmp/base.jpg".to_string())]);
+}
+
+#[test]
+fn test_dms_session_fingerprint_ignores_unrelated_fields() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = test_config_with_dir(temp_dir.path());
+    let session_file = temp_dir.path().join("session.json");
+    let session = DmsSession::new(config);
+
+    let write_session = |wallpaper: &str, extra: &str| {
+        fs::write(
+            &session_file,
+            format!(r#"{{"wallpaperPath": "{wallpaper}", "isLightMode": false{extra}}}"#),
+        )
+        .unwrap();
+    };
+
+    write_session("/tmp/a.jpg", "");
+    SettingsFixture::new().matugen_scheme("scheme-tonal-spot").write_to(temp_dir.path());
+    let base = session.fingerprint().unwrap();
+
+    // UI state DMS keeps next to the wallpaper
+    write_session("/tmp/a.jpg", r#", "nightModeEnabled": true, "doNotDisturb": true"#);
+    assert_eq!(session.fingerprint().unwrap(), base);
+
+    write_session("/tmp/b.jpg", "");
+    assert_ne!(session.fingerprint().unwrap(), base);
 
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(has_changed.unwrap());
+    write_session("/tmp/a.jpg", "");
+    SettingsFixture::new().matugen_scheme("scheme-fidelity").write_to(temp_dir.path());
+    assert_ne!(session.fingerprint().unwrap(), base);
 }
 
 #[test]
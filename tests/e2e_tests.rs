@@ -8,7 +8,7 @@ use std::time::Duration;
 
 use common::{create_test_image, SessionFixture, SettingsFixture};
 use dms_awww::config::Config;
-use dms_awww::dms::DmsSession;
+use dms_awww::dms::{ChangeSet, DmsSession};
 use dms_awww::executor::Executor;
 use tokio::time::sleep;
 
@@ -121,17 +121,15 @@ async fn test_workflow_wallpaper_change_detection() {
 
     SettingsFixture::new().write_to(temp_dir.path());
 
-    let mut session = DmsSession::new(config);
+    let session = DmsSession::new(config);
 
     // First check - should detect initial state
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(has_changed.unwrap(), "Initial check should detect as changed");
+    let initial = session.get_current_state().unwrap();
+    assert!(!ChangeSet::full(None, &initial).is_empty(), "Initial check should detect as changed");
 
     // Second check - no change
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(!has_changed.unwrap(), "Second check should show no change");
+    let current = session.get_current_state().unwrap();
+    assert!(ChangeSet::between(&initial, &current).is_empty(), "Second check should show no change");
 
     // Change the wallpaper
     let test_image2 = temp_dir.path().join("wallpaper2.jpg");
@@ -143,9 +141,9 @@ async fn test_workflow_wallpaper_change_detection() {
 
     // Third check - should detect change
     sleep(Duration::from_millis(10)).await; // Small delay to ensure file timestamp changes
-    let has_changed = session.has_changed();
-    assert!(has_changed.is_ok());
-    assert!(has_changed.unwrap(), "Third check should detect new wallpaper");
+    let changed = session.get_current_state().unwrap();
+    let changes = ChangeSet::between(&current, &changed);
+    assert!(changes.needs_wallpaper(), "Third check should detect new wallpaper");
 }
 
 #[tokio::test]
@@ -213,9 +211,9 @@ async fn test_workflow_error_recovery_invalid_wallpaper() {
     let temp_dir = tempfile::TempDir::new().unwrap();
     let config = test_config_with_dir(temp_dir.path());
 
-    // Use a color value instead of path
+    // Use a malformed color value instead of path
     SessionFixture::new()
-        .wallpaper_path("#ff0000")
+        .wallpaper_path("#ff00")
         .write_to(temp_dir.path());
 
     SettingsFixture::new().write_to(temp_dir.path());
@@ -249,12 +247,17 @@ async fn test_workflow_with_multiple_empty_wallpapers() {
     let session = DmsSession::new(config);
     let state = session.get_current_state();
 
-    // Should succeed with only the valid wallpaper (color values filtered out)
+    // Should succeed with the image and the color (empty values filtered out)
     assert!(state.is_ok());
     let state = state.unwrap();
-    assert_eq!(state.wallpapers.len(), 1);
-    assert_eq!(state.wallpapers[0].monitor, Some("eDP-1".to_string()));
-    assert_eq!(state.wallpapers[0].path, test_image.to_str().unwrap());
+    assert_eq!(state.wallpapers.len(), 2);
+
+    let color = state.wallpapers.iter().find(|w| w.monitor.as_deref() == Some("HDMI-A-1")).unwrap();
+    assert_eq!(color.color(), Some("#ff0000".to_string()));
+
+    let image = state.wallpapers.iter().find(|w| w.monitor.as_deref() == Some("eDP-1")).unwrap();
+    assert_eq!(image.path, test_image.to_str().unwrap());
+    assert!(image.is_valid_image());
 }
 
 #[tokio::test]
@@ -5,9 +5,9 @@ mod common;
 use std::path::Path;
 
 use common::{create_test_image, SessionFixture, SettingsFixture};
-use dms_awww::config::Config;
-use dms_awww::dms::DmsSession;
-use dms_awww::executor::Executor;
+use dms_awww::config::{BackendKind, Config};
+use dms_awww::dms::{ChangeSet, DmsSession};
+use dms_awww::executor::{ApplyTrigger, Executor};
 use dms_awww::error::DmsAwwwError;
 
 /// Create a test config with paths pointing to a temp directory
@@ -23,12 +23,11 @@ fn test_config_with_dir(dir: &Path) -> Config {
 fn test_executor_creation() {
     let config = Config::default();
     let monitors = vec!["HDMI-A-1".to_string(), "DP-1".to_string()];
-    let executor = Executor::new(config, monitors);
+    let _executor = Executor::new(config, monitors);
 
     // Executor is created successfully
     // We can't directly access the fields, but we can check it was created
     // This mainly tests compilation
-    assert!(true);
 }
 
 #[test]
@@ -59,6 +58,17 @@ fn test_check_dependencies_with_both_disabled() {
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+async fn test_check_health_with_backend_disabled() {
+    let mut config = Config::default();
+    config.awww.enabled = false;
+    config.backend.kind = BackendKind::Wpaperd;
+
+    // A disabled backend is never asked
+    let executor = Executor::new(config, vec![]);
+    assert!(executor.check_health().await.is_ok());
+}
+
 #[test]
 fn test_executor_with_awww_disabled() {
     let temp_dir = tempfile::TempDir::new().unwrap();
@@ -79,7 +89,7 @@ fn test_executor_with_awww_disabled() {
     let session = DmsSession::new(config.clone());
     let state = session.get_current_state().unwrap();
 
-    let executor = Executor::new(config, vec![]);
+    let _executor = Executor::new(config, vec![]);
 
     // In a test environment, we can't actually run the commands
     // But we can verify the structure is correct
@@ -108,6 +118,32 @@ fn test_executor_with_matugen_disabled() {
     assert!(executor.check_dependencies().is_ok());
 }
 
+#[tokio::test]
+async fn test_executor_rejects_non_image_wallpaper() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut config = test_config_with_dir(temp_dir.path());
+    config.matugen.enabled = false;
+    config.awww.skip_unchanged = false;
+
+    // Named like an image, but it's text
+    let fake_image = temp_dir.path().join("wallpaper.png");
+    std::fs::write(&fake_image, "not an image").unwrap();
+
+    SessionFixture::new()
+        .wallpaper_path(fake_image.to_str().unwrap())
+        .write_to(temp_dir.path());
+
+    let session = DmsSession::new(config.clone());
+    let state = session.get_current_state().unwrap();
+
+    let executor = Executor::new(config, vec!["DP-1".to_string()]);
+    let err = executor
+        .apply_wallpaper(&state, &ChangeSet::full(None, &state), ApplyTrigger::Change)
+        .await
+        .unwrap_err();
+    assert!(err.user_message().contains("not a supported image"), "{err:?}");
+}
+
 #[test]
 fn test_executor_per_monitor_wallpapers() {
     let temp_dir = tempfile::TempDir::new().unwrap();
@@ -130,7 +166,7 @@ fn test_executor_per_monitor_wallpapers() {
     let session = DmsSession::new(config);
     let state = session.get_current_state().unwrap();
 
-    let executor = Executor::new(Config::default(), vec!["HDMI-A-1".to_string(), "DP-1".to_string()]);
+    let _executor = Executor::new(Config::default(), vec!["HDMI-A-1".to_string(), "DP-1".to_string()]);
 
     // Verify state has correct monitor assignments (order not guaranteed with HashMap)
     assert_eq!(state.wallpapers.len(), 2);
@@ -149,6 +185,7 @@ fn test_wallpaper_change_structure() {
             Wallpaper::for_monitor("/path/to/wp2.jpg".to_string(), "HDMI-A-1".to_string()),
         ],
         is_light_mode: true,
+        matugen_scheme: "scheme-tonal-spot".to_string(),
     };
 
     assert_eq!(change.wallpapers.len(), 2);
@@ -173,8 +210,9 @@ fn test_error_is_critical() {
     let io_err = DmsAwwwError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "test"));
     assert!(!io_err.is_critical());
 
+    // DMS may delete and rewrite session.json; the daemon waits for it instead
     let session_err = DmsAwwwError::SessionFileNotFound("/tmp/test".into());
-    assert!(session_err.is_critical());
+    assert!(!session_err.is_critical());
 
     let no_monitors_err = DmsAwwwError::NoMonitorsDetected;
     assert!(no_monitors_err.is_critical());